@@ -1,46 +1,94 @@
 // enums.rs
-use bevy::{prelude::*, utils::hashbrown::HashMap};
-use bevy_enum_filter::EnumFilter;
-use lazy_static::lazy_static;
-use strum_macros::EnumIter;
+use bevy::prelude::*;
+use serde::Deserialize;
 
-#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy, EnumIter)]
-pub enum CellType {
-    Sand,
-    Stone,
-    BloodStone,
-    Blood,
-}
-
-#[derive(Component, EnumFilter)]
+/// How a material moves, referenced by name from the material file.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Deserialize)]
 pub enum CellPhysicsType {
     Sand,
     Fluid,
-    BloodStone,
+    Gas,
+    /// Spawns cells as configured by the material's `emitter`.
+    Emitter,
+    /// Deletes any loose cell that touches it.
+    Sink,
     Static,
 }
 
-pub const CELL_SIZE: Vec3 = Vec3::new(10.0, 10.0, 10.0);
+impl CellPhysicsType {
+    /// Whether denser materials can sink through cells of this type.
+    pub fn is_displaceable(self) -> bool {
+        matches!(self, CellPhysicsType::Fluid | CellPhysicsType::Gas)
+    }
+}
+
+/// One of the four grid directions.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Deserialize)]
+pub enum CellDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl CellDirection {
+    pub fn offset(self) -> (isize, isize) {
+        match self {
+            CellDirection::Up => (0, 1),
+            CellDirection::Down => (0, -1),
+            CellDirection::Left => (-1, 0),
+            CellDirection::Right => (1, 0),
+        }
+    }
+}
 
-lazy_static! {
-    pub static ref CELL_COLOR: HashMap<CellType, bevy::render::color::Color> = {
-        let mut map = HashMap::new();
-        map.insert(
-            CellType::Sand,
-            bevy::render::color::Color::hex("f6d7b0").unwrap(),
-        );
-        map.insert(
-            CellType::Stone,
-            bevy::render::color::Color::hex("4E5754").unwrap(),
-        );
-        map.insert(
-            CellType::BloodStone,
-            bevy::render::color::Color::hex("8B0000").unwrap(),
-        );
-        map.insert(
-            CellType::Blood,
-            bevy::render::color::Color::hex("5a180e").unwrap(),
-        );
-        map
-    };
+/// Footprint of the paint brush.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+pub enum BrushShape {
+    #[default]
+    Circle,
+    Square,
+    /// A horizontal bar as wide as a square brush.
+    Line,
 }
+
+impl BrushShape {
+    pub const ALL: [BrushShape; 3] = [BrushShape::Circle, BrushShape::Square, BrushShape::Line];
+}
+
+/// What the mouse does in the world.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+pub enum Tool {
+    /// Freehand painting with the brush.
+    #[default]
+    Brush,
+    /// A brush stroke from where the drag started to where it ended.
+    Line,
+    Rectangle,
+    Ellipse,
+    /// Fills the empty region around the clicked cell.
+    Fill,
+    /// Drags out a rectangle to copy or cut.
+    Select,
+    /// Places the clipboard centred on the clicked cell.
+    Paste,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 7] = [
+        Tool::Brush,
+        Tool::Line,
+        Tool::Rectangle,
+        Tool::Ellipse,
+        Tool::Fill,
+        Tool::Select,
+        Tool::Paste,
+    ];
+
+    /// Whether the tool paints a shape dragged out between two cells.
+    pub fn is_shape(self) -> bool {
+        matches!(self, Tool::Line | Tool::Rectangle | Tool::Ellipse)
+    }
+}
+
+pub const CELL_SIZE: Vec3 = Vec3::new(10.0, 10.0, 10.0);
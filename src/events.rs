@@ -1,14 +1,79 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 
-use crate::enums::CellType;
+use crate::{materials::MaterialId, resources::cell_world::CellRect};
 
 #[derive(Event)]
 pub struct SpawnCellEvent {
-    pub pos: Vec2,
-    pub cell_type: CellType,
+    pub pos: (isize, isize),
+    pub material: MaterialId,
 }
 
 #[derive(Event)]
 pub struct RemoveCellEvent {
     pub pos: (isize, isize),
 }
+
+/// Writers for both kinds of cell edit, for systems that paint and erase.
+#[derive(SystemParam)]
+pub struct CellEditEvents<'w> {
+    pub spawn: EventWriter<'w, SpawnCellEvent>,
+    pub remove: EventWriter<'w, RemoveCellEvent>,
+}
+
+impl CellEditEvents<'_> {
+    pub fn paint(&mut self, cells: Vec<(isize, isize)>, material: MaterialId) {
+        self.spawn.send_batch(
+            cells
+                .into_iter()
+                .map(|pos| SpawnCellEvent { pos, material }),
+        );
+    }
+
+    pub fn erase(&mut self, cells: Vec<(isize, isize)>) {
+        self.remove
+            .send_batch(cells.into_iter().map(|pos| RemoveCellEvent { pos }));
+    }
+}
+
+/// Reverts the latest user edit.
+#[derive(Event)]
+pub struct UndoEvent;
+
+/// Reapplies the latest undone edit.
+#[derive(Event)]
+pub struct RedoEvent;
+
+/// Removes every cell as a single edit that can be undone.
+#[derive(Event)]
+pub struct ClearWorldEvent;
+
+/// Copies the selected cells into the clipboard, removing them from the world
+/// as an edit that can be undone when `cut` is set.
+#[derive(Event)]
+pub struct CopySelectionEvent {
+    pub cut: bool,
+}
+
+#[derive(Event)]
+pub struct SaveWorldEvent {
+    pub path: String,
+}
+
+#[derive(Event)]
+pub struct LoadWorldEvent {
+    pub path: String,
+}
+
+#[derive(Event)]
+pub struct ImportImageEvent {
+    pub path: String,
+}
+
+#[derive(Event)]
+pub struct ExportImageEvent {
+    pub path: String,
+    /// Region to export; the bounds of all cells when `None`.
+    pub region: Option<CellRect>,
+    /// Output pixels per cell.
+    pub scale: u32,
+}
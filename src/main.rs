@@ -2,8 +2,13 @@
 mod components;
 mod enums;
 mod events;
+mod materials;
 mod resources;
+mod sand_format;
+mod scene_image;
 mod setup;
+mod shapes;
+mod simulation;
 mod systems;
 mod utils;
 
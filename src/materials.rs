@@ -0,0 +1,504 @@
+// materials.rs
+//! Data-driven material definitions, loaded from `assets/materials.ron`.
+
+use crate::{
+    enums::{CellDirection, CellPhysicsType},
+    resources::cell_world::{Cell, AMBIENT_TEMPERATURE},
+};
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+use std::fmt;
+
+pub const MATERIALS_PATH: &str = "assets/materials.ron";
+
+/// Built-in copy of the material file, used when it cannot be read from disk
+/// (for example in the web build).
+pub const DEFAULT_MATERIALS: &str = include_str!("../assets/materials.ron");
+
+/// Index of a material in the [`MaterialRegistry`].
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy, Default)]
+pub struct MaterialId(pub u16);
+
+#[derive(Deserialize)]
+struct MaterialFile {
+    materials: Vec<MaterialDef>,
+    #[serde(default)]
+    reactions: Vec<ReactionDef>,
+}
+
+#[derive(Deserialize)]
+struct MaterialDef {
+    name: String,
+    colors: Vec<String>,
+    #[serde(default = "default_density")]
+    density: f32,
+    physics: CellPhysicsType,
+    #[serde(default)]
+    flammability: f32,
+    #[serde(default = "default_dispersion")]
+    dispersion: u8,
+    #[serde(default)]
+    viscosity: f32,
+    #[serde(default)]
+    lifetime: Option<u16>,
+    #[serde(default = "default_conductivity")]
+    conductivity: f32,
+    #[serde(default = "default_temperature")]
+    temperature: f32,
+    #[serde(default)]
+    above: Option<PhaseChangeDef>,
+    #[serde(default)]
+    below: Option<PhaseChangeDef>,
+    #[serde(default)]
+    ignition_temperature: Option<f32>,
+    #[serde(default)]
+    burn_time: u16,
+    #[serde(default)]
+    burns_into: Option<String>,
+    #[serde(default)]
+    emitter: Option<EmitterDef>,
+}
+
+#[derive(Deserialize)]
+struct EmitterDef {
+    material: String,
+    direction: CellDirection,
+    rate: f32,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct PhaseChangeDef {
+    temperature: f32,
+    into: String,
+}
+
+#[derive(Deserialize)]
+struct ReactionDef {
+    first: String,
+    second: ReactantDef,
+    first_becomes: ProductDef,
+    second_becomes: ProductDef,
+    probability: f32,
+}
+
+#[derive(Deserialize)]
+enum ReactantDef {
+    Material(String),
+    AnyExcept(Vec<String>),
+}
+
+#[derive(Deserialize)]
+enum ProductDef {
+    Keep,
+    Remove,
+    Become(String),
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_density() -> f32 {
+    1.0
+}
+
+fn default_conductivity() -> f32 {
+    0.2
+}
+
+fn default_temperature() -> f32 {
+    AMBIENT_TEMPERATURE
+}
+
+fn default_dispersion() -> u8 {
+    1
+}
+
+pub struct Material {
+    pub name: String,
+    /// sRGB colours; a cell uses the one selected by its shade.
+    pub colors: Vec<[u8; 4]>,
+    pub density: f32,
+    pub physics: CellPhysicsType,
+    /// Chance per tick, from 0 to 1, that fire spreads into this material.
+    pub flammability: f32,
+    /// How many cells a liquid can flow sideways in one tick.
+    pub dispersion: u8,
+    /// Chance per tick, from 0 to 1, that a liquid holds still instead of
+    /// flowing sideways.
+    pub viscosity: f32,
+    /// Ticks after which a cell of this material disappears.
+    pub lifetime: Option<u16>,
+    /// Fraction of a temperature difference, from 0 to 1, exchanged with each
+    /// neighbour per tick.
+    pub conductivity: f32,
+    /// Temperature of newly created cells.
+    pub temperature: f32,
+    /// Becomes another material when heated above a temperature.
+    pub above: Option<PhaseChange>,
+    /// Becomes another material when cooled below a temperature.
+    pub below: Option<PhaseChange>,
+    /// Temperature at which the material catches fire on its own.
+    pub ignition_temperature: Option<f32>,
+    /// Ticks a cell burns before it is used up.
+    pub burn_time: u16,
+    /// What a cell leaves behind when it has burnt; `None` leaves nothing.
+    pub burns_into: Option<MaterialId>,
+    /// Set for materials with `Emitter` physics.
+    pub emitter: Option<Emitter>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Emitter {
+    pub material: MaterialId,
+    pub direction: CellDirection,
+    /// Cells emitted per second of simulated time.
+    pub rate: f32,
+    /// Emitters can be switched off at runtime from the simulation panel.
+    pub enabled: bool,
+}
+
+impl Emitter {
+    /// Whether to emit on `tick` when the simulation runs at `tick_rate`.
+    /// Rates above the tick rate are capped at one cell per tick.
+    pub fn emits_on(&self, tick: u64, tick_rate: f64) -> bool {
+        if !self.enabled || self.rate <= 0.0 {
+            return false;
+        }
+        let interval = (tick_rate / self.rate as f64).round().max(1.0) as u64;
+        tick.checked_rem(interval) == Some(0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhaseChange {
+    pub temperature: f32,
+    pub into: MaterialId,
+}
+
+impl Material {
+    pub fn color(&self, shade: u8) -> [u8; 4] {
+        self.colors[shade as usize % self.colors.len()]
+    }
+}
+
+/// What a reaction's second cell has to be.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reactant {
+    Material(MaterialId),
+    AnyExcept(Vec<MaterialId>),
+}
+
+impl Reactant {
+    pub fn matches(&self, material: MaterialId) -> bool {
+        match self {
+            Reactant::Material(reactant) => *reactant == material,
+            Reactant::AnyExcept(excluded) => !excluded.contains(&material),
+        }
+    }
+}
+
+/// What a reacting cell turns into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Product {
+    Keep,
+    Remove,
+    Become(MaterialId),
+}
+
+/// Two neighbouring cells that turn into products with some chance per tick.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reaction {
+    pub second: Reactant,
+    pub first_becomes: Product,
+    pub second_becomes: Product,
+    pub probability: f32,
+}
+
+#[derive(Debug)]
+pub enum MaterialError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    InvalidColor { material: String, color: String },
+    NoColors(String),
+    DuplicateName(String),
+    UnknownMaterial { material: String, reference: String },
+    MissingEmitter(String),
+    Empty,
+}
+
+impl fmt::Display for MaterialError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterialError::Io(err) => write!(f, "{err}"),
+            MaterialError::Parse(err) => write!(f, "{err}"),
+            MaterialError::InvalidColor { material, color } => {
+                write!(f, "material {material:?} has invalid colour {color:?}")
+            }
+            MaterialError::NoColors(material) => write!(f, "material {material:?} has no colours"),
+            MaterialError::DuplicateName(material) => {
+                write!(f, "material {material:?} is defined twice")
+            }
+            MaterialError::UnknownMaterial {
+                material,
+                reference,
+            } => write!(
+                f,
+                "material {material:?} refers to unknown material {reference:?}"
+            ),
+            MaterialError::MissingEmitter(material) => {
+                write!(
+                    f,
+                    "material {material:?} has Emitter physics but no emitter"
+                )
+            }
+            MaterialError::Empty => write!(f, "no materials defined"),
+        }
+    }
+}
+
+impl std::error::Error for MaterialError {}
+
+#[derive(Resource)]
+pub struct MaterialRegistry {
+    materials: Vec<Material>,
+    ids: HashMap<String, MaterialId>,
+    /// Reactions indexed by the material of their first cell.
+    reactions: Vec<Vec<Reaction>>,
+}
+
+impl MaterialRegistry {
+    pub fn from_ron(source: &str) -> Result<Self, MaterialError> {
+        let file: MaterialFile = ron::from_str(source).map_err(MaterialError::Parse)?;
+        if file.materials.is_empty() {
+            return Err(MaterialError::Empty);
+        }
+
+        let mut ids = HashMap::new();
+        for (index, def) in file.materials.iter().enumerate() {
+            if ids
+                .insert(def.name.clone(), MaterialId(index as u16))
+                .is_some()
+            {
+                return Err(MaterialError::DuplicateName(def.name.clone()));
+            }
+        }
+        // Materials may name others defined later in the file.
+        let lookup = |material: &str, reference: &str| {
+            ids.get(reference)
+                .copied()
+                .ok_or_else(|| MaterialError::UnknownMaterial {
+                    material: material.to_string(),
+                    reference: reference.to_string(),
+                })
+        };
+        let resolve_phase = |material: &str, phase: &Option<PhaseChangeDef>| {
+            phase
+                .as_ref()
+                .map(|phase| {
+                    Ok(PhaseChange {
+                        temperature: phase.temperature,
+                        into: lookup(material, &phase.into)?,
+                    })
+                })
+                .transpose()
+        };
+
+        let mut materials = Vec::with_capacity(file.materials.len());
+        for def in &file.materials {
+            if def.colors.is_empty() {
+                return Err(MaterialError::NoColors(def.name.clone()));
+            }
+            let colors = def
+                .colors
+                .iter()
+                .map(|color| {
+                    Color::hex(color)
+                        .map(|color| color.as_rgba_u8())
+                        .map_err(|_| MaterialError::InvalidColor {
+                            material: def.name.clone(),
+                            color: color.clone(),
+                        })
+                })
+                .collect::<Result<_, _>>()?;
+
+            if def.physics == CellPhysicsType::Emitter && def.emitter.is_none() {
+                return Err(MaterialError::MissingEmitter(def.name.clone()));
+            }
+            let emitter = def
+                .emitter
+                .as_ref()
+                .map(|emitter| {
+                    Ok(Emitter {
+                        material: lookup(&def.name, &emitter.material)?,
+                        direction: emitter.direction,
+                        rate: emitter.rate,
+                        enabled: emitter.enabled,
+                    })
+                })
+                .transpose()?;
+
+            materials.push(Material {
+                name: def.name.clone(),
+                colors,
+                density: def.density,
+                physics: def.physics,
+                flammability: def.flammability,
+                dispersion: def.dispersion,
+                viscosity: def.viscosity,
+                lifetime: def.lifetime,
+                conductivity: def.conductivity,
+                temperature: def.temperature,
+                above: resolve_phase(&def.name, &def.above)?,
+                below: resolve_phase(&def.name, &def.below)?,
+                ignition_temperature: def.ignition_temperature,
+                burn_time: def.burn_time,
+                burns_into: def
+                    .burns_into
+                    .as_deref()
+                    .map(|reference| lookup(&def.name, reference))
+                    .transpose()?,
+                emitter,
+            });
+        }
+
+        let mut reactions = vec![Vec::new(); materials.len()];
+        for def in &file.reactions {
+            let first = lookup(&def.first, &def.first)?;
+            let product = |product: &ProductDef| match product {
+                ProductDef::Keep => Ok(Product::Keep),
+                ProductDef::Remove => Ok(Product::Remove),
+                ProductDef::Become(into) => lookup(&def.first, into).map(Product::Become),
+            };
+            let second = match &def.second {
+                ReactantDef::Material(name) => Reactant::Material(lookup(&def.first, name)?),
+                ReactantDef::AnyExcept(names) => Reactant::AnyExcept(
+                    names
+                        .iter()
+                        .map(|name| lookup(&def.first, name))
+                        .collect::<Result<_, _>>()?,
+                ),
+            };
+
+            reactions[first.0 as usize].push(Reaction {
+                second,
+                first_becomes: product(&def.first_becomes)?,
+                second_becomes: product(&def.second_becomes)?,
+                probability: def.probability,
+            });
+        }
+
+        Ok(Self {
+            materials,
+            ids,
+            reactions,
+        })
+    }
+
+    /// Reads the material file from disk, falling back to the built-in copy.
+    pub fn load() -> Self {
+        let from_disk = std::fs::read_to_string(MATERIALS_PATH)
+            .map_err(MaterialError::Io)
+            .and_then(|source| Self::from_ron(&source));
+
+        match from_disk {
+            Ok(registry) => registry,
+            Err(err) => {
+                warn!("Using built-in materials, could not load {MATERIALS_PATH}: {err}");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn get(&self, id: MaterialId) -> &Material {
+        &self.materials[id.0 as usize]
+    }
+
+    /// Switches every cell of an emitter material on or off.
+    pub fn set_emitter_enabled(&mut self, id: MaterialId, enabled: bool) {
+        if let Some(emitter) = &mut self.materials[id.0 as usize].emitter {
+            emitter.enabled = enabled;
+        }
+    }
+
+    /// A fresh cell of `id` with a random shade at the material's initial
+    /// temperature.
+    pub fn new_cell(&self, id: MaterialId) -> Cell {
+        self.new_cell_with(id, &mut fastrand::Rng::new())
+    }
+
+    /// Like [`MaterialRegistry::new_cell`], but picks the shade with `rng`.
+    /// The simulation passes its seeded generator so runs stay reproducible.
+    pub fn new_cell_with(&self, id: MaterialId, rng: &mut fastrand::Rng) -> Cell {
+        let mut cell = Cell::new(id).with_random_shade(rng);
+        cell.temperature = self.get(id).temperature;
+        cell
+    }
+
+    /// Reactions in which a cell of `first` is the first reactant.
+    pub fn reactions(&self, first: MaterialId) -> &[Reaction] {
+        &self.reactions[first.0 as usize]
+    }
+
+    pub fn id(&self, name: &str) -> Option<MaterialId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: MaterialId) -> &str {
+        &self.get(id).name
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (MaterialId, &Material)> {
+        self.materials
+            .iter()
+            .enumerate()
+            .map(|(index, material)| (MaterialId(index as u16), material))
+    }
+}
+
+impl Default for MaterialRegistry {
+    fn default() -> Self {
+        Self::from_ron(DEFAULT_MATERIALS).expect("built-in materials are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MaterialError, MaterialRegistry};
+    use crate::enums::CellPhysicsType;
+
+    #[test]
+    fn built_in_materials_parse() {
+        let registry = MaterialRegistry::default();
+        let sand = registry.id("Sand").unwrap();
+
+        assert_eq!(CellPhysicsType::Sand, registry.get(sand).physics);
+        assert_eq!("Sand", registry.name(sand));
+        assert_eq!([0xf6, 0xd7, 0xb0, 0xff], registry.get(sand).color(0));
+    }
+
+    #[test]
+    fn rejects_bad_colours() {
+        let result = MaterialRegistry::from_ron(
+            r#"(materials: [(name: "Goo", colors: ["zz"], physics: Fluid)])"#,
+        );
+        assert!(matches!(result, Err(MaterialError::InvalidColor { .. })));
+    }
+
+    #[test]
+    fn phase_changes_resolve_by_name() {
+        let registry = MaterialRegistry::default();
+        let water = registry.get(registry.id("Water").unwrap());
+
+        assert_eq!(registry.id("Ice"), water.below.map(|phase| phase.into));
+        assert_eq!(registry.id("Steam"), water.above.map(|phase| phase.into));
+
+        let result = MaterialRegistry::from_ron(
+            r#"(materials: [(name: "Goo", colors: ["00ff00"], physics: Fluid,
+                above: Some((temperature: 50.0, into: "Gas")))])"#,
+        );
+        assert!(matches!(result, Err(MaterialError::UnknownMaterial { .. })));
+    }
+}
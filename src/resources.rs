@@ -1,32 +1,201 @@
 pub mod cell_world;
+pub mod clipboard;
+pub mod edit_history;
 
-use crate::enums::{CellType, CELL_COLOR};
-use bevy::{prelude::*, sprite::Mesh2dHandle, utils::hashbrown::HashMap};
+use crate::{
+    enums::{BrushShape, Tool},
+    materials::MaterialId,
+    resources::cell_world::CellRect,
+    shapes::{brush_footprint, ellipse, line, rectangle},
+};
+use bevy::{
+    prelude::*,
+    utils::{hashbrown::HashMap, HashSet},
+};
 use bevy_inspector_egui::{inspector_options::ReflectInspectorOptions, InspectorOptions};
-use strum::IntoEnumIterator;
 
 #[derive(Reflect, Resource, Default, InspectorOptions)]
 #[reflect(Resource, InspectorOptions)]
 pub struct SimulateWorldState {
     pub is_simulating: bool,
+    /// Simulation ticks per second, between `MIN_TICK_RATE` and
+    /// `MAX_TICK_RATE`.
+    #[inspector(min = 1.0, max = 240.0)]
+    pub tick_rate: f64,
+    /// Ticks run per fixed step while simulating, between `MIN_SPEED` and
+    /// `MAX_SPEED`.
+    #[inspector(min = 0.25, max = 8.0)]
+    pub speed: f32,
+    /// Ticks still to run on top of the regular rate, used for stepping while
+    /// paused.
+    pub pending_ticks: u32,
+    tick_budget: f32,
 }
 
 impl SimulateWorldState {
+    pub const MIN_SPEED: f32 = 0.25;
+    pub const MAX_SPEED: f32 = 8.0;
+    pub const MIN_TICK_RATE: f64 = 1.0;
+    pub const MAX_TICK_RATE: f64 = 240.0;
+
     pub fn default() -> Self {
         Self {
             is_simulating: true,
+            tick_rate: 60.0,
+            speed: 1.0,
+            pending_ticks: 0,
+            tick_budget: 0.0,
+        }
+    }
+
+    /// Number of ticks to run during the current fixed step. Fractional speeds
+    /// carry over, so 0.25x runs one tick every fourth step.
+    pub fn take_ticks(&mut self) -> u32 {
+        let mut ticks = std::mem::take(&mut self.pending_ticks);
+        if self.is_simulating {
+            self.tick_budget += self.speed.clamp(Self::MIN_SPEED, Self::MAX_SPEED);
+            let whole_ticks = self.tick_budget.floor();
+            self.tick_budget -= whole_ticks;
+            ticks += whole_ticks as u32;
+        }
+        ticks
+    }
+}
+
+/// The eight grid directions, counter-clockwise from +x.
+pub const GRID_DIRECTIONS: [(isize, isize); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// Pull on loose cells. Cells move along whichever of the eight grid
+/// directions is closest to `direction`.
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct Gravity {
+    pub direction: Vec2,
+    /// Speed gained per tick, in cells per tick. Zero turns gravity off.
+    #[inspector(min = 0.0, max = 2.0)]
+    pub magnitude: f32,
+}
+
+impl Gravity {
+    pub const DEFAULT_MAGNITUDE: f32 = 0.3;
+    pub const MAX_MAGNITUDE: f32 = 2.0;
+
+    /// Direction in degrees counter-clockwise from +x.
+    pub fn angle(&self) -> f32 {
+        self.direction.y.atan2(self.direction.x).to_degrees()
+    }
+
+    pub fn set_angle(&mut self, degrees: f32) {
+        let radians = degrees.to_radians();
+        self.direction = Vec2::new(radians.cos(), radians.sin());
+    }
+
+    /// The grid frame cells move in this tick, or `None` without gravity.
+    pub fn frame(&self) -> Option<GravityFrame> {
+        if self.magnitude <= 0.0 || self.direction == Vec2::ZERO {
+            return None;
+        }
+        let eighths = (self.angle() / 45.0).round() as isize;
+        Some(GravityFrame {
+            down: eighths.rem_euclid(8) as usize,
+            strength: self.magnitude,
+        })
+    }
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Self {
+            direction: Vec2::NEG_Y,
+            magnitude: Self::DEFAULT_MAGNITUDE,
         }
     }
 }
 
+/// Gravity snapped to the grid, for use by movement rules.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GravityFrame {
+    down: usize,
+    pub strength: f32,
+}
+
+impl GravityFrame {
+    /// The grid step `turns` eighths of a turn counter-clockwise from down:
+    /// 0 is down, ±1 the diagonals beside it, ±2 sideways and 4 up.
+    pub fn step(&self, turns: isize) -> (isize, isize) {
+        GRID_DIRECTIONS[(self.down as isize + turns).rem_euclid(8) as usize]
+    }
+
+    /// [`Self::step`] as a unit vector.
+    pub fn unit(&self, turns: isize) -> Vec2 {
+        let (x, y) = self.step(turns);
+        Vec2::new(x as f32, y as f32).normalize()
+    }
+
+    pub fn is_diagonal(&self) -> bool {
+        self.down % 2 == 1
+    }
+}
+
+/// Source of every random choice the simulation makes, so runs from the same
+/// seed and world play out identically.
+#[derive(Resource)]
+pub struct SimulationRng {
+    pub seed: u64,
+    pub rng: fastrand::Rng,
+}
+
+impl SimulationRng {
+    pub const DEFAULT_SEED: u64 = 0x5eed;
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+
+    /// Restarts the sequence from `seed`.
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Self::new(seed);
+    }
+}
+
+impl Default for SimulationRng {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_SEED)
+    }
+}
+
+/// Number of simulation ticks run since startup.
+#[derive(Reflect, Resource, Default)]
+#[reflect(Resource)]
+pub struct SimulationTick {
+    pub tick: u64,
+}
+
 #[derive(Resource)]
 pub struct EguiHoverState {
     pub is_hovered: bool,
+    /// A text field has keyboard focus, so shortcuts should stay quiet.
+    pub is_typing: bool,
 }
 
 impl EguiHoverState {
     pub fn default() -> Self {
-        Self { is_hovered: false }
+        Self {
+            is_hovered: false,
+            is_typing: false,
+        }
     }
 }
 
@@ -37,56 +206,101 @@ pub struct CursorPosition {
 
 impl CursorPosition {
     pub fn default() -> Self {
-        return Self { pos: Vec2::ZERO };
+        Self { pos: Vec2::ZERO }
     }
 }
 
+pub struct ChunkTexture {
+    pub sprite: Entity,
+    pub image: Handle<Image>,
+}
+
+/// Sprite and texture drawing each allocated chunk, keyed by chunk coordinates.
+#[derive(Resource, Default)]
+pub struct ChunkTextures {
+    pub chunks: HashMap<(isize, isize), ChunkTexture>,
+}
+
+/// Footprint used for painting and erasing.
 #[derive(Resource)]
-pub struct CellMesh {
-    pub mesh: Mesh2dHandle,
+pub struct Brush {
+    pub radius: u32,
+    pub shape: BrushShape,
+    /// Share of the footprint filled each frame: 1 paints solid, lower values
+    /// spray.
+    pub density: f32,
 }
 
-impl CellMesh {
-    pub fn from_world(mut meshes: ResMut<Assets<Mesh>>) -> Self {
-        let mesh = meshes.add(Mesh::from(shape::Quad::default())).into();
+impl Brush {
+    pub const MAX_RADIUS: u32 = 32;
 
-        CellMesh { mesh }
+    /// Cells to paint this frame for a stroke moving from `from` to `to`,
+    /// thinned out when spraying. The footprint is stamped along the line
+    /// between them so fast drags leave no gaps.
+    pub fn stroke(&self, from: (isize, isize), to: (isize, isize)) -> Vec<(isize, isize)> {
+        let mut seen = HashSet::new();
+        let mut cells: Vec<_> = line(from, to)
+            .into_iter()
+            .flat_map(|center| brush_footprint(center, self.radius, self.shape))
+            .filter(|&pos| seen.insert(pos))
+            .collect();
+        if self.density < 1.0 {
+            cells.retain(|_| fastrand::f32() < self.density);
+        }
+        cells
     }
 }
 
-#[derive(Resource)]
-pub struct CellTypeToSpawn {
-    pub type_to_select: CellType,
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            radius: 0,
+            shape: BrushShape::Circle,
+            density: 1.0,
+        }
+    }
 }
 
-impl CellTypeToSpawn {
-    pub fn default() -> Self {
-        CellTypeToSpawn {
-            type_to_select: CellType::Sand,
+/// The selected tool and the cell its current drag started from.
+#[derive(Resource, Default)]
+pub struct ToolState {
+    pub tool: Tool,
+    /// Whether rectangles and ellipses are painted solid or as outlines.
+    pub filled: bool,
+    pub drag_start: Option<(isize, isize)>,
+    /// Rectangle picked with the select tool for copying and cutting.
+    pub selection: Option<CellRect>,
+}
+
+impl ToolState {
+    /// Largest region a fill may cover; bigger regions are left untouched.
+    pub const MAX_FILL_CELLS: usize = 64 * 64 * 16;
+
+    /// Cells covered by the selected shape tool dragged from `from` to `to`.
+    pub fn shape(
+        &self,
+        brush: &Brush,
+        from: (isize, isize),
+        to: (isize, isize),
+    ) -> Vec<(isize, isize)> {
+        match self.tool {
+            Tool::Line => brush.stroke(from, to),
+            Tool::Rectangle => rectangle(from, to, self.filled),
+            Tool::Ellipse => ellipse(from, to, self.filled),
+            Tool::Brush | Tool::Fill | Tool::Select | Tool::Paste => Vec::new(),
         }
     }
 }
 
 #[derive(Resource)]
-pub struct SandMaterials {
-    pub materials: Vec<Handle<ColorMaterial>>,
-    pub color_ids: HashMap<CellType, usize>,
+pub struct CellTypeToSpawn {
+    pub type_to_select: MaterialId,
 }
 
-impl SandMaterials {
-    pub fn from_world(mut materials: ResMut<Assets<ColorMaterial>>) -> Self {
-        let mut sand_materials: Vec<Handle<ColorMaterial>> = vec![];
-        let mut color_ids: HashMap<CellType, usize> = HashMap::new();
-
-        for cell_type in CellType::iter() {
-            sand_materials.push(materials.add(ColorMaterial::from(CELL_COLOR[&cell_type])));
-            color_ids.insert(cell_type, sand_materials.len() - 1);
+impl CellTypeToSpawn {
+    pub fn default() -> Self {
+        CellTypeToSpawn {
+            type_to_select: MaterialId::default(),
         }
-        let sand_materials_resource: SandMaterials = SandMaterials {
-            materials: sand_materials,
-            color_ids,
-        };
-
-        sand_materials_resource
     }
 }
@@ -1,110 +1,435 @@
-use std::ops::Range;
-
-use bevy::prelude::*;
+use crate::materials::MaterialId;
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
 use grid::Grid;
 
+/// Width and height of a chunk in cells.
+pub const CHUNK_SIZE: isize = 64;
+
+/// Temperature every new cell starts with.
+pub const AMBIENT_TEMPERATURE: f32 = 20.0;
+
+/// Simulation state of a single grain, stored by value in the chunk grids.
+///
+/// Cells are plain data rather than ECS entities; entities are left for
+/// special objects that need components of their own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cell {
+    pub material: MaterialId,
+    /// Picks one of the material's colours.
+    pub shade: u8,
+    pub flags: u8,
+    pub velocity: Vec2,
+    pub temperature: f32,
+    /// Ticks lived for materials with a lifetime, or ticks spent burning.
+    pub age: u16,
+    /// World tick the cell was last stepped or moved in, so a grain is never
+    /// stepped twice in one tick.
+    pub stepped: u32,
+}
+
+impl Cell {
+    /// Set while the cell is on fire.
+    pub const BURNING: u8 = 1 << 0;
+
+    pub fn new(material: MaterialId) -> Self {
+        Self {
+            material,
+            shade: 0,
+            flags: 0,
+            velocity: Vec2::ZERO,
+            temperature: AMBIENT_TEMPERATURE,
+            age: 0,
+            stepped: 0,
+        }
+    }
+
+    pub fn with_random_shade(mut self, rng: &mut fastrand::Rng) -> Self {
+        self.shade = rng.u8(..);
+        self
+    }
+}
+
+/// Inclusive rectangle of cell coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CellRect {
+    pub min: (isize, isize),
+    pub max: (isize, isize),
+}
+
+impl CellRect {
+    /// The rectangle spanned by two corners given in any order.
+    pub fn from_corners(a: (isize, isize), b: (isize, isize)) -> Self {
+        Self {
+            min: (a.0.min(b.0), a.1.min(b.1)),
+            max: (a.0.max(b.0), a.1.max(b.1)),
+        }
+    }
+
+    /// Grows the rectangle just enough to contain `pos`.
+    pub fn including(self, pos: (isize, isize)) -> Self {
+        Self {
+            min: (self.min.0.min(pos.0), self.min.1.min(pos.1)),
+            max: (self.max.0.max(pos.0), self.max.1.max(pos.1)),
+        }
+    }
+
+    pub fn width(&self) -> isize {
+        self.max.0 - self.min.0 + 1
+    }
+
+    pub fn height(&self) -> isize {
+        self.max.1 - self.min.1 + 1
+    }
+}
+
+struct Chunk {
+    cells: Grid<Option<Cell>>,
+    cell_count: usize,
+}
+
+impl Chunk {
+    fn new() -> Self {
+        Self {
+            cells: Grid::new(CHUNK_SIZE as usize, CHUNK_SIZE as usize),
+            cell_count: 0,
+        }
+    }
+}
+
+/// Sparse cell storage split into `CHUNK_SIZE` x `CHUNK_SIZE` chunks.
+///
+/// Chunks are allocated on the first insert and dropped again once their last
+/// cell is removed, so the world has no fixed edge.
+///
+/// Every change marks its chunk dirty, together with the neighbouring chunk
+/// when the changed cell lies on a chunk border. Physics only visits chunks
+/// that were dirtied during the previous tick, so settled regions fall asleep.
 #[derive(Resource)]
 pub struct CellWorld {
-    quadrant_i: Grid<Option<Entity>>,   // Positive row, positive col
-    quadrant_ii: Grid<Option<Entity>>,  // Negative row, positive col
-    quadrant_iii: Grid<Option<Entity>>, // Negative row, negative col
-    quadrant_iv: Grid<Option<Entity>>,  // Positive row, negative col
+    chunks: HashMap<(isize, isize), Chunk>,
+    dirty_chunks: HashSet<(isize, isize)>,
+    active_chunks: HashSet<(isize, isize)>,
+    changed_cells: HashSet<(isize, isize)>,
+    tick: u32,
     pub cell_count: usize,
-    pub size_rows: Range<isize>,
-    pub size_cols: Range<isize>,
 }
 
 impl CellWorld {
     pub fn default() -> Self {
-        let rows = 1000;
-        let cols = 1000;
         Self {
-            quadrant_i: Grid::new(rows, cols),
-            quadrant_ii: Grid::new(rows, cols),
-            quadrant_iii: Grid::new(rows, cols),
-            quadrant_iv: Grid::new(rows, cols),
+            chunks: HashMap::new(),
+            dirty_chunks: HashSet::new(),
+            active_chunks: HashSet::new(),
+            changed_cells: HashSet::new(),
+            tick: 0,
             cell_count: 0,
-            size_rows: -(rows as isize)..rows as isize,
-            size_cols: -(cols as isize)..cols as isize,
         }
     }
 
-    pub fn insert(&mut self, row: isize, col: isize, entity: Option<Entity>) {
-        if self.check_bounds((row, col)) {
-            return;
-        }
-        let (row_idx, col_idx) = (row.abs() as usize, col.abs() as usize);
+    pub fn insert(&mut self, x: isize, y: isize, cell: Option<Cell>) {
+        let (chunk_pos, local) = Self::split_coords(x, y);
 
-        match (row >= 0, col >= 0) {
-            (true, true) => self.quadrant_i[(row_idx, col_idx)] = entity,
-            (false, true) => self.quadrant_ii[(row_idx - 1, col_idx)] = entity,
-            (false, false) => self.quadrant_iii[(row_idx - 1, col_idx - 1)] = entity,
-            (true, false) => self.quadrant_iv[(row_idx, col_idx - 1)] = entity,
+        let previous = match cell {
+            Some(_) => {
+                let chunk = self.chunks.entry(chunk_pos).or_insert_with(Chunk::new);
+                let previous = std::mem::replace(&mut chunk.cells[local], cell);
+                if previous.is_none() {
+                    chunk.cell_count += 1;
+                }
+                previous
+            }
+            None => {
+                let Some(chunk) = self.chunks.get_mut(&chunk_pos) else {
+                    return;
+                };
+                let previous = chunk.cells[local].take();
+                if previous.is_some() {
+                    chunk.cell_count -= 1;
+                    if chunk.cell_count == 0 {
+                        self.chunks.remove(&chunk_pos);
+                    }
+                }
+                previous
+            }
+        };
+
+        match (previous.is_some(), cell.is_some()) {
+            (false, true) => self.cell_count += 1,
+            (true, false) => self.cell_count -= 1,
+            _ => {}
         }
-        if entity.is_some() {
-            self.cell_count += 1
-        } else {
-            self.cell_count -= 1;
+        if previous != cell {
+            self.mark_dirty(chunk_pos, local);
+            self.changed_cells.insert((x, y));
         }
     }
 
-    pub fn is_cell_empty(&self, pos: (isize, isize)) -> bool {
-        if self.check_bounds(pos) {
-            return true;
-        }
-        match self.get(pos.0, pos.1) {
-            None => true,
-            Some(_) => false,
+    /// Exchanges the contents of two positions, either of which may be empty.
+    /// Moved cells are marked as updated for the current tick.
+    pub fn swap(&mut self, a: (isize, isize), b: (isize, isize)) {
+        let cell_a = self.get(a.0, a.1).map(|cell| self.updated(cell));
+        let cell_b = self.get(b.0, b.1).map(|cell| self.updated(cell));
+
+        // Fill before emptying so a shared chunk is not freed in between.
+        if cell_a.is_some() {
+            self.insert(b.0, b.1, cell_a);
+            self.insert(a.0, a.1, cell_b);
+        } else {
+            self.insert(a.0, a.1, cell_b);
+            self.insert(b.0, b.1, cell_a);
         }
     }
 
-    pub fn get(&self, row: isize, col: isize) -> Option<Entity> {
-        if self.check_bounds((row, col)) {
-            return None;
+    /// Keeps the chunk containing `(x, y)` active next tick without changing
+    /// anything, for cells that may still act even though nothing moved.
+    pub fn wake(&mut self, x: isize, y: isize) {
+        let (chunk_pos, local) = Self::split_coords(x, y);
+        self.mark_dirty(chunk_pos, local);
+    }
+
+    /// Keeps every chunk active next tick, for changes that affect the whole
+    /// world such as a new gravity direction.
+    pub fn wake_all(&mut self) {
+        self.dirty_chunks.extend(self.chunks.keys().copied());
+    }
+
+    /// Wakes the chunk containing `local` and every neighbouring chunk whose
+    /// border touches it.
+    fn mark_dirty(&mut self, chunk_pos: (isize, isize), local: (usize, usize)) {
+        let last = CHUNK_SIZE as usize - 1;
+        let x_offsets = match local.0 {
+            0 => -1..=0,
+            x if x == last => 0..=1,
+            _ => 0..=0,
+        };
+
+        for x_offset in x_offsets {
+            let y_offsets = match local.1 {
+                0 => -1..=0,
+                y if y == last => 0..=1,
+                _ => 0..=0,
+            };
+            for y_offset in y_offsets {
+                self.dirty_chunks
+                    .insert((chunk_pos.0 + x_offset, chunk_pos.1 + y_offset));
+            }
         }
-        let (row_idx, col_idx) = (row.abs() as usize, col.abs() as usize);
+    }
 
-        match (row >= 0, col >= 0) {
-            (true, true) => self.quadrant_i[(row_idx, col_idx)],
-            (false, true) => self.quadrant_ii[(row_idx - 1, col_idx)],
-            (false, false) => self.quadrant_iii[(row_idx - 1, col_idx - 1)],
-            (true, false) => self.quadrant_iv[(row_idx, col_idx - 1)],
+    /// Starts a new simulation tick: chunks dirtied since the last call become
+    /// the active set and the dirty set is cleared.
+    pub fn advance_activity(&mut self) {
+        self.active_chunks = std::mem::take(&mut self.dirty_chunks);
+        self.tick = self.tick.wrapping_add(1);
+    }
+
+    pub fn active_chunk_count(&self) -> usize {
+        self.active_chunks.len()
+    }
+
+    /// Active chunks that still hold cells, ordered bottom to top and left to
+    /// right.
+    pub fn active_chunks(&self) -> Vec<(isize, isize)> {
+        let mut chunks: Vec<_> = self
+            .active_chunks
+            .iter()
+            .copied()
+            .filter(|chunk_pos| self.chunks.contains_key(chunk_pos))
+            .collect();
+        chunks.sort_unstable_by_key(|&(x, y)| (y, x));
+        chunks
+    }
+
+    /// Whether `cell` has already been stepped or moved during the current
+    /// tick.
+    pub fn is_updated(&self, cell: &Cell) -> bool {
+        cell.stepped == self.tick
+    }
+
+    /// Flags the cell at `(x, y)` as stepped this tick. This is bookkeeping
+    /// only, so it neither keeps the chunk awake nor redraws the cell.
+    pub fn mark_updated(&mut self, x: isize, y: isize) {
+        let Some(cell) = self.get(x, y) else {
+            return;
+        };
+        let cell = self.updated(cell);
+        let (chunk_pos, local) = Self::split_coords(x, y);
+        if let Some(chunk) = self.chunks.get_mut(&chunk_pos) {
+            chunk.cells[local] = Some(cell);
         }
     }
 
-    pub fn check_bounds(&self, pos: (isize, isize)) -> bool {
-        if !(self.size_rows.contains(&pos.0) && self.size_cols.contains(&pos.1)) {
-            return true;
+    pub fn updated(&self, mut cell: Cell) -> Cell {
+        cell.stepped = self.tick;
+        cell
+    }
+
+    /// Drains the positions whose contents changed since the last call.
+    pub fn take_changes(&mut self) -> HashSet<(isize, isize)> {
+        std::mem::take(&mut self.changed_cells)
+    }
+
+    pub fn is_cell_empty(&self, pos: (isize, isize)) -> bool {
+        self.get(pos.0, pos.1).is_none()
+    }
+
+    pub fn get(&self, x: isize, y: isize) -> Option<Cell> {
+        let (chunk_pos, local) = Self::split_coords(x, y);
+
+        self.chunks
+            .get(&chunk_pos)
+            .and_then(|chunk| chunk.cells[local])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ((isize, isize), &Cell)> {
+        self.chunks.iter().flat_map(|(&(chunk_x, chunk_y), chunk)| {
+            chunk
+                .cells
+                .indexed_iter()
+                .filter_map(move |((x, y), cell)| {
+                    cell.as_ref().map(|cell| {
+                        (
+                            (
+                                chunk_x * CHUNK_SIZE + x as isize,
+                                chunk_y * CHUNK_SIZE + y as isize,
+                            ),
+                            cell,
+                        )
+                    })
+                })
+        })
+    }
+
+    /// Removes every cell, recording each one as a change.
+    pub fn clear(&mut self) {
+        let positions: Vec<_> = self.iter().map(|(pos, _)| pos).collect();
+        for (x, y) in positions {
+            self.insert(x, y, None);
         }
-        false
+    }
+
+    /// Smallest rectangle containing every cell, or `None` for an empty world.
+    pub fn bounds(&self) -> Option<CellRect> {
+        let mut positions = self.iter().map(|(pos, _)| pos);
+        let first = positions.next()?;
+        Some(positions.fold(CellRect::from_corners(first, first), CellRect::including))
+    }
+
+    /// Allocated chunks, ordered bottom to top and left to right.
+    pub fn chunk_positions(&self) -> Vec<(isize, isize)> {
+        let mut chunks: Vec<_> = self.chunks.keys().copied().collect();
+        chunks.sort_unstable_by_key(|&(x, y)| (y, x));
+        chunks
+    }
+
+    pub fn has_chunk(&self, chunk_pos: (isize, isize)) -> bool {
+        self.chunks.contains_key(&chunk_pos)
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Splits world cell coordinates into chunk coordinates and the position
+    /// inside that chunk.
+    pub fn split_coords(x: isize, y: isize) -> ((isize, isize), (usize, usize)) {
+        (
+            (x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE)),
+            (
+                x.rem_euclid(CHUNK_SIZE) as usize,
+                y.rem_euclid(CHUNK_SIZE) as usize,
+            ),
+        )
+    }
+
+    /// World coordinates of every cell slot in a chunk, bottom row first.
+    pub fn chunk_cell_coords(chunk_pos: (isize, isize)) -> impl Iterator<Item = (isize, isize)> {
+        let origin = (chunk_pos.0 * CHUNK_SIZE, chunk_pos.1 * CHUNK_SIZE);
+        (0..CHUNK_SIZE)
+            .flat_map(move |y| (0..CHUNK_SIZE).map(move |x| (origin.0 + x, origin.1 + y)))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use bevy::ecs::entity::Entity;
+    use crate::materials::MaterialId;
+    use crate::resources::cell_world::{Cell, CellWorld};
 
-    use crate::resources::cell_world::CellWorld;
+    // Indices of the bundled materials.
+    const SAND: MaterialId = MaterialId(0);
+    const STONE: MaterialId = MaterialId(1);
+    const BLOOD: MaterialId = MaterialId(3);
 
     #[test]
     fn if_cell_world_is_empty_fn() {
         let mut cell_world = CellWorld::default();
-        cell_world.insert(10, 10, Some(Entity::from_raw(10)));
+        cell_world.insert(10, 10, Some(Cell::new(SAND)));
         println!("Cell at (1, 1): {:?}", cell_world.get(1, 1));
-        assert_eq!(true, cell_world.is_cell_empty((1, 1)));
-        assert_eq!(false, cell_world.is_cell_empty((10, 10)));
+        assert!(cell_world.is_cell_empty((1, 1)));
+        assert!(!cell_world.is_cell_empty((10, 10)));
     }
 
     #[test]
     fn col_and_row_test() {
-        let world = CellWorld::default();
+        let mut world = CellWorld::default();
 
         let row = 1001;
         let col = -1700;
+        world.insert(row, col, Some(Cell::new(STONE)));
 
-        assert_eq!(
-            true,
-            world.size_rows.contains(&row) && world.size_cols.contains(&col)
-        )
+        assert_eq!(Some(STONE), world.get(row, col).map(|cell| cell.material));
+        assert!(world.is_cell_empty((row - 1, col)));
+    }
+
+    #[test]
+    fn empty_chunks_are_freed() {
+        let mut world = CellWorld::default();
+
+        world.insert(-1, -1, Some(Cell::new(SAND)));
+        world.insert(0, 0, Some(Cell::new(SAND)));
+        world.insert(0, 0, Some(Cell::new(BLOOD)));
+        assert_eq!(2, world.chunk_count());
+        assert_eq!(2, world.cell_count);
+
+        world.insert(-1, -1, None);
+        world.insert(-1, -1, None);
+        assert_eq!(1, world.chunk_count());
+        assert_eq!(1, world.cell_count);
+        assert_eq!(Some(BLOOD), world.get(0, 0).map(|cell| cell.material));
+    }
+
+    #[test]
+    fn border_changes_wake_neighbour_chunks() {
+        let mut world = CellWorld::default();
+
+        world.insert(10, 10, Some(Cell::new(SAND)));
+        world.insert(64, 127, Some(Cell::new(SAND)));
+        world.advance_activity();
+        // Only chunks holding cells are visited; the woken neighbours (0, 1),
+        // (1, 2) and (0, 2) are empty and therefore skipped.
+        assert_eq!(vec![(0, 0), (1, 1)], world.active_chunks());
+        assert_eq!(5, world.active_chunk_count());
+
+        world.advance_activity();
+        assert_eq!(0, world.active_chunk_count());
+    }
+
+    #[test]
+    fn swap_moves_cells_across_chunks() {
+        let mut world = CellWorld::default();
+        world.insert(0, 0, Some(Cell::new(SAND)));
+        world.take_changes();
+
+        world.swap((0, 0), (0, -1));
+
+        let moved = world.get(0, -1).unwrap();
+        assert_eq!(SAND, moved.material);
+        assert!(world.is_updated(&moved));
+        assert!(world.is_cell_empty((0, 0)));
+        assert_eq!(1, world.chunk_count());
+        assert_eq!(2, world.take_changes().len());
     }
 }
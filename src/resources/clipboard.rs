@@ -0,0 +1,116 @@
+use super::cell_world::{Cell, CellRect, CellWorld};
+use bevy::prelude::*;
+
+/// Cells copied out of the world, positioned relative to the bottom-left
+/// corner of the copied rectangle. Empty positions are not stored, so pasting
+/// leaves whatever is behind them in place.
+#[derive(Resource, Default)]
+pub struct Clipboard {
+    cells: Vec<((isize, isize), Cell)>,
+    width: isize,
+    height: isize,
+}
+
+impl Clipboard {
+    pub fn copy(world: &CellWorld, rect: CellRect) -> Self {
+        let cells = (rect.min.1..=rect.max.1)
+            .flat_map(|y| (rect.min.0..=rect.max.0).map(move |x| (x, y)))
+            .filter_map(|(x, y)| {
+                let cell = world.get(x, y)?;
+                Some(((x - rect.min.0, y - rect.min.1), cell))
+            })
+            .collect();
+
+        Self {
+            cells,
+            width: rect.width(),
+            height: rect.height(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn size(&self) -> (isize, isize) {
+        (self.width, self.height)
+    }
+
+    /// Turns the contents a quarter turn counter-clockwise.
+    pub fn rotate(&mut self) {
+        let height = self.height;
+        for ((x, y), _) in &mut self.cells {
+            (*x, *y) = (height - 1 - *y, *x);
+        }
+        (self.width, self.height) = (self.height, self.width);
+    }
+
+    /// Flips the contents left to right.
+    pub fn mirror(&mut self) {
+        let width = self.width;
+        for ((x, _), _) in &mut self.cells {
+            *x = width - 1 - *x;
+        }
+    }
+
+    /// Bottom-left corner that centres the contents on `center`.
+    pub fn origin(&self, center: (isize, isize)) -> (isize, isize) {
+        (center.0 - self.width / 2, center.1 - self.height / 2)
+    }
+
+    /// The stored cells at their world positions when pasted with the
+    /// bottom-left corner at `origin`.
+    pub fn placed(
+        &self,
+        origin: (isize, isize),
+    ) -> impl Iterator<Item = ((isize, isize), Cell)> + '_ {
+        self.cells
+            .iter()
+            .map(move |&((x, y), cell)| ((origin.0 + x, origin.1 + y), cell))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Clipboard;
+    use crate::materials::MaterialId;
+    use crate::resources::cell_world::{Cell, CellRect, CellWorld};
+
+    const SAND: MaterialId = MaterialId(0);
+    const STONE: MaterialId = MaterialId(1);
+
+    fn contents(clipboard: &Clipboard) -> Vec<((isize, isize), MaterialId)> {
+        let mut cells: Vec<_> = clipboard
+            .cells
+            .iter()
+            .map(|&(pos, cell)| (pos, cell.material))
+            .collect();
+        cells.sort_unstable_by_key(|&(pos, _)| pos);
+        cells
+    }
+
+    #[test]
+    fn copies_rotates_and_mirrors() {
+        let mut world = CellWorld::default();
+        world.insert(10, 20, Some(Cell::new(STONE)));
+        world.insert(12, 20, Some(Cell::new(SAND)));
+        world.insert(50, 50, Some(Cell::new(SAND)));
+
+        let mut clipboard = Clipboard::copy(&world, CellRect::from_corners((10, 20), (12, 21)));
+        assert_eq!((3, 2), clipboard.size());
+        assert_eq!(vec![((0, 0), STONE), ((2, 0), SAND)], contents(&clipboard));
+
+        clipboard.rotate();
+        assert_eq!((2, 3), clipboard.size());
+        assert_eq!(vec![((1, 0), STONE), ((1, 2), SAND)], contents(&clipboard));
+
+        clipboard.mirror();
+        assert_eq!(vec![((0, 0), STONE), ((0, 2), SAND)], contents(&clipboard));
+
+        let placed: Vec<_> = clipboard
+            .placed(clipboard.origin((5, 5)))
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(vec![(4, 4), (4, 6)], placed);
+    }
+}
@@ -0,0 +1,251 @@
+use super::cell_world::{Cell, CellWorld};
+use bevy::{prelude::*, utils::HashMap};
+use std::collections::VecDeque;
+
+/// One position overwritten by an edit.
+#[derive(Clone, Copy, Debug)]
+struct CellChange {
+    pos: (isize, isize),
+    before: Option<Cell>,
+    after: Option<Cell>,
+}
+
+/// Undo and redo stacks of user edits, each stored as the cells it changed.
+///
+/// Edits write through [`EditHistory::set`], which records the change into the
+/// pending edit until [`EditHistory::commit`] closes it. The simulation writes
+/// to the `CellWorld` directly and is never recorded, so undoing puts back the
+/// edited cells as they were without rewinding anything else. Positions the
+/// simulation has since filled with another material are left alone.
+#[derive(Resource)]
+pub struct EditHistory {
+    max_changes: usize,
+    pending: HashMap<(isize, isize), CellChange>,
+    undo: VecDeque<Vec<CellChange>>,
+    redo: Vec<Vec<CellChange>>,
+    change_count: usize,
+}
+
+impl EditHistory {
+    pub const DEFAULT_MAX_CHANGES: usize = 1 << 20;
+
+    /// Writes `cell` at `pos` as part of the pending edit.
+    pub fn set(&mut self, world: &mut CellWorld, pos: (isize, isize), cell: Option<Cell>) {
+        let before = world.get(pos.0, pos.1);
+        self.pending
+            .entry(pos)
+            .or_insert(CellChange {
+                pos,
+                before,
+                after: before,
+            })
+            .after = cell;
+        world.insert(pos.0, pos.1, cell);
+    }
+
+    /// Closes the pending edit, making it the next step to undo.
+    pub fn commit(&mut self) {
+        let changes: Vec<_> = self
+            .pending
+            .drain()
+            .map(|(_, change)| change)
+            .filter(|change| change.before != change.after)
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+
+        self.change_count += changes.len();
+        self.undo.push_back(changes);
+        for step in self.redo.drain(..) {
+            self.change_count -= step.len();
+        }
+        self.trim();
+    }
+
+    /// Reverts the latest edit, returning whether there was one.
+    pub fn undo(&mut self, world: &mut CellWorld) -> bool {
+        self.commit();
+        let Some(step) = self.undo.pop_back() else {
+            return false;
+        };
+        for change in &step {
+            restore(world, change.pos, change.after, change.before);
+        }
+        self.redo.push(step);
+        true
+    }
+
+    /// Reapplies the latest undone edit, returning whether there was one.
+    pub fn redo(&mut self, world: &mut CellWorld) -> bool {
+        self.commit();
+        let Some(step) = self.redo.pop() else {
+            return false;
+        };
+        for change in &step {
+            restore(world, change.pos, change.before, change.after);
+        }
+        self.undo.push_back(step);
+        true
+    }
+
+    /// Forgets every step, e.g. after the whole world was replaced.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.undo.clear();
+        self.redo.clear();
+        self.change_count = 0;
+    }
+
+    /// Most cell changes kept across all steps; the oldest steps are dropped
+    /// to stay below it, including a single edit larger than the cap.
+    pub fn max_changes(&self) -> usize {
+        self.max_changes
+    }
+
+    pub fn set_max_changes(&mut self, max_changes: usize) {
+        self.max_changes = max_changes;
+        self.trim();
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Cell changes currently held across all steps.
+    pub fn change_count(&self) -> usize {
+        self.change_count
+    }
+
+    /// Approximate memory held by the recorded steps.
+    pub fn memory_bytes(&self) -> usize {
+        self.change_count * std::mem::size_of::<CellChange>()
+    }
+
+    fn trim(&mut self) {
+        while self.change_count > self.max_changes {
+            let Some(step) = self.undo.pop_front() else {
+                break;
+            };
+            self.change_count -= step.len();
+        }
+    }
+}
+
+/// Writes `to` at `pos` if it still holds the material of `from`.
+fn restore(world: &mut CellWorld, pos: (isize, isize), from: Option<Cell>, to: Option<Cell>) {
+    let material = |cell: Option<Cell>| cell.map(|cell| cell.material);
+    if material(world.get(pos.0, pos.1)) == material(from) {
+        world.insert(pos.0, pos.1, to);
+    }
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self {
+            max_changes: Self::DEFAULT_MAX_CHANGES,
+            pending: HashMap::default(),
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            change_count: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EditHistory;
+    use crate::materials::MaterialId;
+    use crate::resources::cell_world::{Cell, CellWorld};
+
+    const SAND: MaterialId = MaterialId(0);
+    const STONE: MaterialId = MaterialId(1);
+
+    fn material_at(world: &CellWorld, x: isize) -> Option<MaterialId> {
+        world.get(x, 0).map(|cell| cell.material)
+    }
+
+    #[test]
+    fn undo_and_redo_restore_edited_cells() {
+        let mut world = CellWorld::default();
+        let mut history = EditHistory::default();
+        world.insert(0, 0, Some(Cell::new(STONE)));
+
+        history.set(&mut world, (0, 0), None);
+        history.set(&mut world, (1, 0), Some(Cell::new(SAND)));
+        history.set(&mut world, (1, 0), Some(Cell::new(STONE)));
+        history.commit();
+        history.set(&mut world, (2, 0), Some(Cell::new(SAND)));
+        history.commit();
+        assert_eq!(2, history.undo_len());
+        assert_eq!(3, history.change_count());
+
+        assert!(history.undo(&mut world));
+        assert_eq!(None, material_at(&world, 2));
+        assert!(history.undo(&mut world));
+        assert_eq!(Some(STONE), material_at(&world, 0));
+        assert_eq!(None, material_at(&world, 1));
+        assert!(!history.undo(&mut world));
+
+        assert!(history.redo(&mut world));
+        assert_eq!(None, material_at(&world, 0));
+        assert_eq!(Some(STONE), material_at(&world, 1));
+
+        // A new edit discards the steps left to redo.
+        history.set(&mut world, (3, 0), Some(Cell::new(SAND)));
+        history.commit();
+        assert!(!history.redo(&mut world));
+        assert_eq!(3, history.change_count());
+    }
+
+    #[test]
+    fn cells_changed_since_are_left_alone() {
+        let mut world = CellWorld::default();
+        let mut history = EditHistory::default();
+
+        history.set(&mut world, (0, 0), Some(Cell::new(SAND)));
+        history.set(&mut world, (1, 0), Some(Cell::new(SAND)));
+        history.set(&mut world, (2, 0), Some(Cell::new(SAND)));
+        history.commit();
+
+        // The simulation moves one grain away and lets stone take the place
+        // of another.
+        world.swap((1, 0), (1, -1));
+        world.insert(2, 0, Some(Cell::new(STONE)));
+
+        assert!(history.undo(&mut world));
+        assert_eq!(None, material_at(&world, 0));
+        assert_eq!(Some(SAND), world.get(1, -1).map(|cell| cell.material));
+        assert_eq!(Some(STONE), material_at(&world, 2));
+
+        world.insert(1, 0, Some(Cell::new(STONE)));
+        assert!(history.redo(&mut world));
+        assert_eq!(Some(SAND), material_at(&world, 0));
+        assert_eq!(Some(STONE), material_at(&world, 1));
+        assert_eq!(Some(STONE), material_at(&world, 2));
+    }
+
+    #[test]
+    fn oldest_steps_are_dropped_over_the_cap() {
+        let mut world = CellWorld::default();
+        let mut history = EditHistory::default();
+        history.set_max_changes(2);
+
+        for x in 0..3 {
+            history.set(&mut world, (x, 0), Some(Cell::new(SAND)));
+            history.commit();
+        }
+        assert_eq!(2, history.undo_len());
+
+        // Edits that change nothing are not recorded.
+        let cell = world.get(0, 0);
+        history.set(&mut world, (0, 0), None);
+        history.set(&mut world, (0, 0), cell);
+        history.commit();
+        assert_eq!(2, history.undo_len());
+    }
+}
@@ -0,0 +1,302 @@
+// sand_format.rs
+//! Reading and writing worlds as `.sand` files.
+//!
+//! All values are little endian:
+//!
+//! ```text
+//! magic        b"SAND"
+//! version      u16
+//! materials    u16 count, then per material: u8 name length + UTF-8 name
+//!              (names longer than 255 bytes cannot be saved)
+//! chunks       u32 count, then per chunk:
+//!                i32 chunk x, i32 chunk y
+//!                runs covering the chunk's CHUNK_SIZE² slots, bottom row first:
+//!                  u16 run length
+//!                  u16 material index + 1, or 0 for empty slots
+//!                  non-empty runs only: f32 velocity x, f32 velocity y,
+//!                                       f32 temperature,
+//!                                       u8 shade, u8 flags, u16 age
+//! ```
+
+use crate::{
+    materials::MaterialRegistry,
+    resources::cell_world::{Cell, CellWorld, CHUNK_SIZE},
+};
+use bevy::prelude::*;
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+const MAGIC: &[u8; 4] = b"SAND";
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Cells decoded from a file, keyed by their world position.
+pub type SavedCells = Vec<((isize, isize), Cell)>;
+
+#[derive(Debug)]
+pub enum SandFormatError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnknownMaterial(String),
+    /// Names are stored with a one byte length.
+    MaterialNameTooLong(String),
+    InvalidMaterialIndex(u16),
+    InvalidRun,
+}
+
+impl fmt::Display for SandFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SandFormatError::Io(err) => write!(f, "{err}"),
+            SandFormatError::BadMagic => write!(f, "not a .sand file"),
+            SandFormatError::UnsupportedVersion(version) => {
+                write!(f, "unsupported .sand version {version}")
+            }
+            SandFormatError::UnknownMaterial(name) => write!(f, "unknown material {name:?}"),
+            SandFormatError::MaterialNameTooLong(name) => {
+                write!(f, "material name {name:?} is longer than 255 bytes")
+            }
+            SandFormatError::InvalidMaterialIndex(index) => {
+                write!(f, "material index {index} is not in the material table")
+            }
+            SandFormatError::InvalidRun => write!(f, "run overflows its chunk"),
+        }
+    }
+}
+
+impl std::error::Error for SandFormatError {}
+
+impl From<io::Error> for SandFormatError {
+    fn from(err: io::Error) -> Self {
+        SandFormatError::Io(err)
+    }
+}
+
+/// Cells compare equal for run-length purposes when everything that is
+/// saved matches; the tick a cell was last stepped in is not saved.
+fn same_saved_state(a: &Option<Cell>, b: &Option<Cell>) -> bool {
+    match (a, b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => {
+            a.material == b.material
+                && a.velocity == b.velocity
+                && a.temperature == b.temperature
+                && a.shade == b.shade
+                && a.flags == b.flags
+                && a.age == b.age
+        }
+        _ => false,
+    }
+}
+
+pub fn write_world<W: Write>(
+    world: &CellWorld,
+    materials: &MaterialRegistry,
+    writer: &mut W,
+) -> Result<(), SandFormatError> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+
+    // Cells store registry indices directly, so the table is the registry in
+    // order.
+    writer.write_all(&(materials.iter().count() as u16).to_le_bytes())?;
+    for (_, material) in materials.iter() {
+        let name = &material.name;
+        let name_length = u8::try_from(name.len())
+            .map_err(|_| SandFormatError::MaterialNameTooLong(name.clone()))?;
+        writer.write_all(&[name_length])?;
+        writer.write_all(name.as_bytes())?;
+    }
+
+    let chunks = world.chunk_positions();
+    writer.write_all(&(chunks.len() as u32).to_le_bytes())?;
+    for chunk_pos in chunks {
+        writer.write_all(&(chunk_pos.0 as i32).to_le_bytes())?;
+        writer.write_all(&(chunk_pos.1 as i32).to_le_bytes())?;
+
+        let mut slots = CellWorld::chunk_cell_coords(chunk_pos)
+            .map(|(x, y)| world.get(x, y))
+            .peekable();
+        while let Some(cell) = slots.next() {
+            let mut run_length: u16 = 1;
+            while slots
+                .peek()
+                .is_some_and(|next| same_saved_state(&cell, next))
+            {
+                slots.next();
+                run_length += 1;
+            }
+
+            writer.write_all(&run_length.to_le_bytes())?;
+            match cell {
+                None => writer.write_all(&0u16.to_le_bytes())?,
+                Some(cell) => {
+                    writer.write_all(&(cell.material.0 + 1).to_le_bytes())?;
+                    writer.write_all(&cell.velocity.x.to_le_bytes())?;
+                    writer.write_all(&cell.velocity.y.to_le_bytes())?;
+                    writer.write_all(&cell.temperature.to_le_bytes())?;
+                    writer.write_all(&[cell.shade, cell.flags])?;
+                    writer.write_all(&cell.age.to_le_bytes())?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Decodes a `.sand` stream into the cells it contains. Materials are matched
+/// by name, so files survive materials being added to the registry.
+pub fn read_world<R: Read>(
+    reader: &mut R,
+    materials: &MaterialRegistry,
+) -> Result<SavedCells, SandFormatError> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(SandFormatError::BadMagic);
+    }
+    let version = read_u16(reader)?;
+    if version != FORMAT_VERSION {
+        return Err(SandFormatError::UnsupportedVersion(version));
+    }
+
+    let material_count = read_u16(reader)?;
+    let mut material_table = Vec::with_capacity(material_count as usize);
+    for _ in 0..material_count {
+        let mut name_length = [0; 1];
+        reader.read_exact(&mut name_length)?;
+        let mut name = vec![0; name_length[0] as usize];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8_lossy(&name).into_owned();
+
+        let material = materials
+            .id(&name)
+            .ok_or(SandFormatError::UnknownMaterial(name))?;
+        material_table.push(material);
+    }
+
+    let chunk_count = read_u32(reader)?;
+    let mut cells = Vec::new();
+    for _ in 0..chunk_count {
+        let chunk_pos = (read_i32(reader)? as isize, read_i32(reader)? as isize);
+        let mut slots = CellWorld::chunk_cell_coords(chunk_pos);
+        let mut remaining = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+        while remaining > 0 {
+            let run_length = read_u16(reader)? as usize;
+            if run_length == 0 || run_length > remaining {
+                return Err(SandFormatError::InvalidRun);
+            }
+            remaining -= run_length;
+
+            let material_index = read_u16(reader)?;
+            if material_index == 0 {
+                slots.nth(run_length - 1);
+                continue;
+            }
+
+            let material = *material_table
+                .get(material_index as usize - 1)
+                .ok_or(SandFormatError::InvalidMaterialIndex(material_index))?;
+            let mut cell = Cell::new(material);
+            cell.velocity = Vec2::new(read_f32(reader)?, read_f32(reader)?);
+            cell.temperature = read_f32(reader)?;
+            let mut shade_and_flags = [0; 2];
+            reader.read_exact(&mut shade_and_flags)?;
+            [cell.shade, cell.flags] = shade_and_flags;
+            cell.age = read_u16(reader)?;
+
+            cells.extend(slots.by_ref().take(run_length).map(|pos| (pos, cell)));
+        }
+    }
+
+    Ok(cells)
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_i32<R: Read>(reader: &mut R) -> io::Result<i32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+fn read_f32<R: Read>(reader: &mut R) -> io::Result<f32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_world, write_world, SandFormatError};
+    use crate::{
+        materials::MaterialRegistry,
+        resources::cell_world::{Cell, CellWorld},
+    };
+
+    #[test]
+    fn round_trip_preserves_cells() {
+        let materials = MaterialRegistry::default();
+        let mut world = CellWorld::default();
+        for x in -70..70 {
+            world.insert(x, -3, Some(Cell::new(materials.id("Stone").unwrap())));
+        }
+        let mut hot_blood = Cell::new(materials.id("Blood").unwrap());
+        hot_blood.temperature = 80.0;
+        world.insert(5, 100, Some(hot_blood));
+        let mut burning_wood = Cell::new(materials.id("Wood").unwrap());
+        burning_wood.shade = 2;
+        burning_wood.flags = Cell::BURNING;
+        burning_wood.age = 17;
+        world.insert(6, 100, Some(burning_wood));
+
+        let mut bytes = Vec::new();
+        write_world(&world, &materials, &mut bytes).unwrap();
+        let cells = read_world(&mut bytes.as_slice(), &materials).unwrap();
+
+        assert_eq!(world.cell_count, cells.len());
+        for (pos, cell) in cells {
+            let original = world.get(pos.0, pos.1).unwrap();
+            assert_eq!(original.material, cell.material);
+            assert_eq!(original.temperature, cell.temperature);
+            assert_eq!(original.shade, cell.shade);
+            assert_eq!(original.flags, cell.flags);
+            assert_eq!(original.age, cell.age);
+        }
+    }
+
+    #[test]
+    fn rejects_foreign_files() {
+        let result = read_world(&mut b"cargo tree".as_slice(), &MaterialRegistry::default());
+        assert!(matches!(result, Err(SandFormatError::BadMagic)));
+    }
+
+    #[test]
+    fn rejects_material_names_too_long_to_store() {
+        let name = "Goo".repeat(100);
+        let materials = MaterialRegistry::from_ron(&format!(
+            r#"(materials: [(name: "{name}", colors: ["00ff00"], physics: Fluid)])"#
+        ))
+        .unwrap();
+
+        let result = write_world(&CellWorld::default(), &materials, &mut Vec::new());
+        assert!(matches!(
+            result,
+            Err(SandFormatError::MaterialNameTooLong(_))
+        ));
+    }
+}
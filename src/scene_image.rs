@@ -0,0 +1,241 @@
+// scene_image.rs
+//! Conversion between images and cells, one pixel per cell.
+
+use crate::{
+    materials::{MaterialId, MaterialRegistry},
+    resources::cell_world::{CellRect, CellWorld},
+};
+use bevy::prelude::*;
+use image::{Rgba, RgbaImage};
+use std::{fmt, path::Path};
+
+/// Materials decoded from an image, keyed by pixel position.
+pub type ImportedCells = Vec<((isize, isize), MaterialId)>;
+
+/// Most pixels an export may have, 256 MiB of RGBA. The world has no edges,
+/// so a single stray cell can stretch its bounds far beyond what fits.
+pub const MAX_EXPORT_PIXELS: u64 = 1 << 26;
+
+#[derive(Debug)]
+pub enum SceneImageError {
+    Image(image::ImageError),
+    /// The region times the scale exceeds [`MAX_EXPORT_PIXELS`].
+    TooLarge(CellRect),
+}
+
+impl fmt::Display for SceneImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneImageError::Image(err) => write!(f, "{err}"),
+            SceneImageError::TooLarge(rect) => write!(
+                f,
+                "region from {:?} to {:?} is larger than {MAX_EXPORT_PIXELS} pixels",
+                rect.min, rect.max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SceneImageError {}
+
+impl From<image::ImageError> for SceneImageError {
+    fn from(err: image::ImageError) -> Self {
+        SceneImageError::Image(err)
+    }
+}
+
+pub struct PaletteEntry {
+    pub material: MaterialId,
+    pub name: String,
+    pub color: [u8; 3],
+    pub enabled: bool,
+}
+
+/// Maps pixel colours to the material with the nearest palette colour.
+#[derive(Resource)]
+pub struct ImportPalette {
+    pub entries: Vec<PaletteEntry>,
+    /// Pixels with alpha below this stay empty when `ignore_transparent` is set.
+    pub alpha_threshold: u8,
+    pub ignore_transparent: bool,
+}
+
+impl ImportPalette {
+    /// One entry per material, using its first colour.
+    pub fn from_materials(materials: &MaterialRegistry) -> Self {
+        let entries = materials
+            .iter()
+            .map(|(material, definition)| {
+                let [r, g, b, _] = definition.color(0);
+                PaletteEntry {
+                    material,
+                    name: definition.name.clone(),
+                    color: [r, g, b],
+                    enabled: true,
+                }
+            })
+            .collect();
+
+        Self {
+            entries,
+            alpha_threshold: 128,
+            ignore_transparent: true,
+        }
+    }
+
+    pub fn nearest(&self, pixel: [u8; 4]) -> Option<MaterialId> {
+        if self.ignore_transparent && pixel[3] < self.alpha_threshold {
+            return None;
+        }
+
+        self.entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .min_by_key(|entry| {
+                entry
+                    .color
+                    .iter()
+                    .zip(&pixel[..3])
+                    .map(|(&a, &b)| (a as i32 - b as i32).pow(2))
+                    .sum::<i32>()
+            })
+            .map(|entry| entry.material)
+    }
+}
+
+/// Cells for every mapped pixel, with the image's bottom-left pixel at
+/// `(0, 0)` and rows increasing upwards.
+pub fn image_to_cells(image: &RgbaImage, palette: &ImportPalette) -> ImportedCells {
+    let height = image.height() as isize;
+
+    image
+        .enumerate_pixels()
+        .filter_map(|(x, y, pixel)| {
+            palette
+                .nearest(pixel.0)
+                .map(|material| ((x as isize, height - 1 - y as isize), material))
+        })
+        .collect()
+}
+
+pub fn import_png<P: AsRef<Path>>(
+    path: P,
+    palette: &ImportPalette,
+) -> Result<ImportedCells, image::ImageError> {
+    let image = image::open(path)?.into_rgba8();
+    Ok(image_to_cells(&image, palette))
+}
+
+/// Draws `rect` with every cell as a `scale` x `scale` block in its material
+/// colour. Empty cells are transparent.
+pub fn world_to_image(
+    world: &CellWorld,
+    materials: &MaterialRegistry,
+    rect: CellRect,
+    scale: u32,
+) -> Result<RgbaImage, SceneImageError> {
+    let scale = scale.max(1);
+    let pixels = |min: isize, max: isize| {
+        let cells = max.checked_sub(min)?.checked_add(1)?;
+        u32::try_from(cells).ok()?.checked_mul(scale)
+    };
+    let (width, height) = pixels(rect.min.0, rect.max.0)
+        .zip(pixels(rect.min.1, rect.max.1))
+        .filter(|&(width, height)| width as u64 * height as u64 <= MAX_EXPORT_PIXELS)
+        .ok_or(SceneImageError::TooLarge(rect))?;
+
+    Ok(RgbaImage::from_fn(width, height, |px, py| {
+        let x = rect.min.0 + (px / scale) as isize;
+        let y = rect.max.1 - (py / scale) as isize;
+        world.get(x, y).map_or(Rgba([0, 0, 0, 0]), |cell| {
+            Rgba(materials.get(cell.material).color(cell.shade))
+        })
+    }))
+}
+
+/// Writes `rect`, or the bounds of every cell when `None`, to a PNG.
+pub fn export_png<P: AsRef<Path>>(
+    world: &CellWorld,
+    materials: &MaterialRegistry,
+    rect: Option<CellRect>,
+    scale: u32,
+    path: P,
+) -> Result<(), SceneImageError> {
+    let rect = rect
+        .or_else(|| world.bounds())
+        .unwrap_or(CellRect::from_corners((0, 0), (0, 0)));
+    world_to_image(world, materials, rect, scale)?.save(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{image_to_cells, world_to_image, ImportPalette, SceneImageError};
+    use crate::{
+        materials::MaterialRegistry,
+        resources::cell_world::{Cell, CellRect, CellWorld},
+    };
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn pixels_map_to_nearest_material() {
+        let materials = MaterialRegistry::default();
+        let stone = materials.id("Stone").unwrap();
+        let sand = materials.id("Sand").unwrap();
+        let [r, g, b, _] = materials.get(stone).color(0);
+        let mut image = RgbaImage::new(2, 2);
+        image.put_pixel(0, 0, Rgba([r + 3, g, b.saturating_sub(2), 255]));
+        image.put_pixel(1, 1, Rgba([250, 215, 175, 255]));
+
+        let mut cells = image_to_cells(&image, &ImportPalette::from_materials(&materials));
+        cells.sort_by_key(|&(pos, _)| pos);
+
+        assert_eq!(vec![((0, 1), stone), ((1, 0), sand)], cells);
+    }
+
+    #[test]
+    fn export_then_import_reproduces_world() {
+        let materials = MaterialRegistry::default();
+        let blood = materials.id("Blood").unwrap();
+        let stone = materials.id("Stone").unwrap();
+        let sand = materials.id("Sand").unwrap();
+        let mut world = CellWorld::default();
+        world.insert(-2, 5, Some(Cell::new(blood)));
+        world.insert(3, 7, Some(Cell::new(stone)));
+        world.insert(0, 6, Some(Cell::new(sand)));
+
+        let rect = world.bounds().unwrap();
+        let image = world_to_image(&world, &materials, rect, 1).unwrap();
+        assert_eq!((6, 3), image.dimensions());
+
+        let mut cells = image_to_cells(&image, &ImportPalette::from_materials(&materials));
+        cells.sort_by_key(|&(pos, _)| pos);
+        assert_eq!(
+            vec![((0, 0), blood), ((2, 1), sand), ((5, 2), stone)],
+            cells
+        );
+
+        let upscaled = world_to_image(&world, &materials, rect, 4).unwrap();
+        assert_eq!((24, 12), upscaled.dimensions());
+        assert_eq!(image.get_pixel(5, 0), upscaled.get_pixel(23, 3));
+    }
+
+    #[test]
+    fn oversized_regions_are_refused() {
+        let materials = MaterialRegistry::default();
+        let sand = materials.id("Sand").unwrap();
+        let mut world = CellWorld::default();
+        world.insert(0, 0, Some(Cell::new(sand)));
+        world.insert(1_000_000, 1_000_000, Some(Cell::new(sand)));
+
+        let stray = world.bounds().unwrap();
+        let small = CellRect::from_corners((0, 0), (9, 9));
+        let endless = CellRect::from_corners((isize::MIN, 0), (isize::MAX, 0));
+        for (rect, scale) in [(stray, 1), (small, u32::MAX), (endless, 1)] {
+            assert!(matches!(
+                world_to_image(&world, &materials, rect, scale),
+                Err(SceneImageError::TooLarge(_))
+            ));
+        }
+    }
+}
@@ -1,27 +1,50 @@
 // setup.rs
 use crate::{
     components::MainCamera,
-    enums::CellPhysicsType,
-    events::{RemoveCellEvent, SpawnCellEvent},
+    enums::Tool,
+    events::{
+        ClearWorldEvent, CopySelectionEvent, ExportImageEvent, ImportImageEvent, LoadWorldEvent,
+        RedoEvent, RemoveCellEvent, SaveWorldEvent, SpawnCellEvent, UndoEvent,
+    },
+    materials::MaterialRegistry,
     resources::{
-        cell_world::CellWorld, CellMesh, CellTypeToSpawn, CursorPosition, EguiHoverState,
-        SandMaterials, SimulateWorldState,
+        cell_world::CellWorld, clipboard::Clipboard, edit_history::EditHistory, Brush,
+        CellTypeToSpawn, ChunkTextures, CursorPosition, EguiHoverState, Gravity,
+        SimulateWorldState, SimulationRng, SimulationTick, ToolState,
     },
+    scene_image::ImportPalette,
+    simulation::{advance_tick, apply_tick_rate, run_simulation, Simulation, SimulationSet},
     systems::{
         camera::{move_camera, zoom_camera},
-        cell_management::{remove_cell, spawn_cell, spawn_cell_on_touch, spawn_or_remove_cell_on_click},
-        physics::{blood_stone_physics, fluid_physics, sand_physics},
+        cell_management::{
+            remove_cell, spawn_cell, spawn_cell_on_touch, spawn_or_remove_cell_on_click,
+        },
+        combustion::burn_cells,
+        heat::conduct_heat,
+        history::{clear_world, finish_edit, undo_or_redo, EditSet},
+        input_handling::{
+            brush_shortcuts, clipboard_shortcuts, history_shortcuts, simulation_shortcuts,
+        },
+        physics::{advance_cell_world_activity, simulate_cells, wake_on_gravity_change},
+        reactions::react_cells,
+        render::{
+            draw_brush_preview, draw_selection_preview, draw_shape_preview, update_chunk_textures,
+        },
+        tools::{
+            copy_selection, tool_selected, use_fill_tool, use_paste_tool, use_select_tool,
+            use_shape_tool,
+        },
         ui_systems::{
-            check_egui_hover, check_is_empty_on_mouse_pos, my_cursor_system, show_cell_count,
-            spawn_cell_type,
+            brush_ui, check_egui_hover, check_is_empty_on_mouse_pos, history_ui, my_cursor_system,
+            show_cell_count, simulation_controls, spawn_cell_type, tool_ui, world_file_ui,
         },
         window_management::set_window_icon,
+        world_io::{export_scene_image, import_scene_image, load_world, save_world},
     },
 };
 use bevy::{prelude::*, window::PresentMode};
 
 use bevy_egui::EguiPlugin;
-use bevy_enum_filter::prelude::AddEnumFilter;
 use bevy_fps_counter::FpsCounterPlugin;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
@@ -29,6 +52,8 @@ pub struct SetupPlugin;
 
 impl Plugin for SetupPlugin {
     fn build(&self, app: &mut App) {
+        let materials = MaterialRegistry::load();
+
         app.add_systems(Startup, set_window_icon)
             .add_systems(Startup, setup)
             .insert_resource(ClearColor(Color::rgb(0.0, 0.170, 0.253)))
@@ -43,45 +68,140 @@ impl Plugin for SetupPlugin {
                 ..default()
             }))
             .add_plugins(EguiPlugin)
-            .add_enum_filter::<CellPhysicsType>()
-            .add_systems(Update, spawn_cell_type)
-            .add_systems(Update, my_cursor_system)
-            .add_systems(Update, spawn_or_remove_cell_on_click)
+            .add_systems(Update, (spawn_cell_type, tool_ui))
+            .add_systems(Update, (brush_ui, brush_shortcuts, draw_brush_preview))
+            .init_resource::<Brush>()
+            .add_systems(Update, my_cursor_system.before(EditSet::Queue))
+            .init_resource::<ToolState>()
+            .init_resource::<EditHistory>()
+            // All user edits happen in Update, between ticks, so every tick
+            // sees a consistent world.
+            .configure_sets(
+                Update,
+                (EditSet::Queue, EditSet::Apply, EditSet::Direct).chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    history_ui,
+                    history_shortcuts.in_set(EditSet::Queue),
+                    (undo_or_redo, clear_world).in_set(EditSet::Direct),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        spawn_or_remove_cell_on_click.run_if(tool_selected(Tool::Brush)),
+                        use_shape_tool.run_if(|tools: Res<ToolState>| tools.tool.is_shape()),
+                        use_fill_tool.run_if(tool_selected(Tool::Fill)),
+                        spawn_cell_on_touch.run_if(tool_selected(Tool::Brush)),
+                        use_select_tool.run_if(tool_selected(Tool::Select)),
+                    )
+                        .in_set(EditSet::Queue),
+                    use_paste_tool
+                        .run_if(tool_selected(Tool::Paste))
+                        .in_set(EditSet::Direct),
+                    draw_shape_preview,
+                ),
+            )
+            .add_systems(
+                Update,
+                (spawn_cell, remove_cell, finish_edit)
+                    .chain()
+                    .in_set(EditSet::Apply),
+            )
+            .init_resource::<Clipboard>()
+            .add_systems(
+                Update,
+                (
+                    clipboard_shortcuts.in_set(EditSet::Queue),
+                    copy_selection.in_set(EditSet::Direct),
+                    draw_selection_preview,
+                ),
+            )
             .add_plugins(WorldInspectorPlugin::new())
-            .add_systems(Update, (sand_physics, fluid_physics, blood_stone_physics))
+            .configure_sets(
+                Simulation,
+                (
+                    SimulationSet::Activity,
+                    SimulationSet::Physics,
+                    SimulationSet::Reactions,
+                    SimulationSet::Heat,
+                    SimulationSet::Combustion,
+                    SimulationSet::Tick,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Simulation,
+                (
+                    advance_cell_world_activity.in_set(SimulationSet::Activity),
+                    simulate_cells.in_set(SimulationSet::Physics),
+                    react_cells.in_set(SimulationSet::Reactions),
+                    conduct_heat.in_set(SimulationSet::Heat),
+                    burn_cells.in_set(SimulationSet::Combustion),
+                    advance_tick.in_set(SimulationSet::Tick),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    apply_tick_rate,
+                    wake_on_gravity_change,
+                    update_chunk_textures,
+                ),
+            )
+            .add_systems(Update, (simulation_controls, simulation_shortcuts))
+            .add_systems(
+                Update,
+                (
+                    world_file_ui,
+                    save_world,
+                    (load_world, import_scene_image).in_set(EditSet::Direct),
+                    export_scene_image,
+                ),
+            )
+            .insert_resource(ImportPalette::from_materials(&materials))
+            .insert_resource(materials)
             .add_systems(Update, move_camera)
             .insert_resource(CellWorld::default())
+            .init_resource::<ChunkTextures>()
             .insert_resource(CellTypeToSpawn::default())
             .insert_resource(EguiHoverState::default())
             .insert_resource(CursorPosition::default())
             .insert_resource(SimulateWorldState::default())
             .register_type::<SimulateWorldState>()
+            .init_resource::<Gravity>()
+            .init_resource::<SimulationRng>()
+            .register_type::<Gravity>()
+            .init_resource::<SimulationTick>()
+            .register_type::<SimulationTick>()
             .add_plugins(FpsCounterPlugin)
-            .add_systems(Update, spawn_cell_on_touch)
             .add_systems(Update, zoom_camera)
             .add_systems(Update, show_cell_count)
             .add_systems(
-                FixedUpdate,
+                Update,
                 (
-                    spawn_cell,
-                    remove_cell,
                     check_is_empty_on_mouse_pos,
                     //cell_list_ui,
                     check_egui_hover,
                 ),
             )
+            .add_systems(FixedUpdate, run_simulation)
             .add_event::<SpawnCellEvent>()
-            .add_event::<RemoveCellEvent>();
+            .add_event::<RemoveCellEvent>()
+            .add_event::<SaveWorldEvent>()
+            .add_event::<LoadWorldEvent>()
+            .add_event::<ImportImageEvent>()
+            .add_event::<ExportImageEvent>()
+            .add_event::<UndoEvent>()
+            .add_event::<RedoEvent>()
+            .add_event::<ClearWorldEvent>()
+            .add_event::<CopySelectionEvent>();
     }
 }
 
-fn setup(
-    mut commands: Commands,
-    materials: ResMut<Assets<ColorMaterial>>,
-    meshes: ResMut<Assets<Mesh>>,
-) {
+fn setup(mut commands: Commands) {
     commands.spawn((Camera2dBundle::default(), MainCamera));
-
-    commands.insert_resource(SandMaterials::from_world(materials));
-    commands.insert_resource(CellMesh::from_world(meshes));
 }
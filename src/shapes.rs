@@ -0,0 +1,184 @@
+// shapes.rs
+//! Rasterisers for the editing tools. Everything works in cell coordinates.
+
+use crate::{enums::BrushShape, resources::cell_world::CellRect};
+use bevy::utils::HashSet;
+use std::collections::VecDeque;
+
+/// Cells covered by a brush of `radius` centred on `center`.
+pub fn brush_footprint(
+    center: (isize, isize),
+    radius: u32,
+    shape: BrushShape,
+) -> Vec<(isize, isize)> {
+    let r = radius as isize;
+    match shape {
+        // `r * r + r` rounds the outline so small circles are not diamonds.
+        BrushShape::Circle => (-r..=r)
+            .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+            .filter(|(dx, dy)| dx * dx + dy * dy <= r * r + r)
+            .map(|(dx, dy)| (center.0 + dx, center.1 + dy))
+            .collect(),
+        BrushShape::Square => (-r..=r)
+            .flat_map(|dy| (-r..=r).map(move |dx| (center.0 + dx, center.1 + dy)))
+            .collect(),
+        BrushShape::Line => (-r..=r).map(|dx| (center.0 + dx, center.1)).collect(),
+    }
+}
+
+/// Cells on the line from `from` to `to`, both included, using Bresenham's
+/// algorithm. Consecutive cells touch at least diagonally.
+pub fn line(from: (isize, isize), to: (isize, isize)) -> Vec<(isize, isize)> {
+    let dx = (to.0 - from.0).abs();
+    let dy = -(to.1 - from.1).abs();
+    let step_x = if from.0 < to.0 { 1 } else { -1 };
+    let step_y = if from.1 < to.1 { 1 } else { -1 };
+
+    let mut cells = Vec::with_capacity((dx.max(-dy) + 1) as usize);
+    let (mut x, mut y) = from;
+    let mut error = dx + dy;
+    loop {
+        cells.push((x, y));
+        if (x, y) == to {
+            return cells;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// Cells of the rectangle spanned by two corners, or only its border when
+/// not `filled`.
+pub fn rectangle(a: (isize, isize), b: (isize, isize), filled: bool) -> Vec<(isize, isize)> {
+    let rect = CellRect::from_corners(a, b);
+    rect_cells(rect)
+        .filter(|&(x, y)| {
+            filled || x == rect.min.0 || x == rect.max.0 || y == rect.min.1 || y == rect.max.1
+        })
+        .collect()
+}
+
+/// Cells of the ellipse inscribed in the rectangle spanned by two corners, or
+/// only its outline when not `filled`. The outline is the set of inside cells
+/// with an outside neighbour, so it never has gaps.
+pub fn ellipse(a: (isize, isize), b: (isize, isize), filled: bool) -> Vec<(isize, isize)> {
+    let rect = CellRect::from_corners(a, b);
+    let radius_x = rect.width() as f32 / 2.0;
+    let radius_y = rect.height() as f32 / 2.0;
+    let center_x = rect.min.0 as f32 + radius_x;
+    let center_y = rect.min.1 as f32 + radius_y;
+    let inside = |(x, y): (isize, isize)| {
+        let dx = (x as f32 + 0.5 - center_x) / radius_x;
+        let dy = (y as f32 + 0.5 - center_y) / radius_y;
+        dx * dx + dy * dy <= 1.0
+    };
+
+    rect_cells(rect)
+        .filter(|&pos| inside(pos))
+        .filter(|&(x, y)| {
+            filled
+                || [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                    .into_iter()
+                    .any(|neighbour| !inside(neighbour))
+        })
+        .collect()
+}
+
+fn rect_cells(rect: CellRect) -> impl Iterator<Item = (isize, isize)> {
+    (rect.min.1..=rect.max.1).flat_map(move |y| (rect.min.0..=rect.max.0).map(move |x| (x, y)))
+}
+
+/// The 4-connected region around `start` whose cells satisfy `matches`, or
+/// `None` when it is larger than `limit` cells, as open space in an unbounded
+/// world always is.
+pub fn flood_fill(
+    start: (isize, isize),
+    limit: usize,
+    matches: impl Fn((isize, isize)) -> bool,
+) -> Option<Vec<(isize, isize)>> {
+    if !matches(start) {
+        return Some(Vec::new());
+    }
+
+    let mut region = vec![start];
+    let mut seen = HashSet::from_iter([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some((x, y)) = queue.pop_front() {
+        for neighbour in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            if !seen.insert(neighbour) || !matches(neighbour) {
+                continue;
+            }
+            if region.len() == limit {
+                return None;
+            }
+            region.push(neighbour);
+            queue.push_back(neighbour);
+        }
+    }
+    Some(region)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{brush_footprint, ellipse, flood_fill, line, rectangle};
+    use crate::enums::BrushShape;
+
+    #[test]
+    fn brush_footprints() {
+        assert_eq!(vec![(3, 4)], brush_footprint((3, 4), 0, BrushShape::Circle));
+        assert_eq!(9, brush_footprint((0, 0), 1, BrushShape::Square).len());
+        assert_eq!(
+            vec![(-2, 7), (-1, 7), (0, 7), (1, 7), (2, 7)],
+            brush_footprint((0, 7), 2, BrushShape::Line)
+        );
+
+        let circle = brush_footprint((0, 0), 3, BrushShape::Circle);
+        assert!(circle.contains(&(3, 0)) && circle.contains(&(0, -3)));
+        assert!(!circle.contains(&(3, 3)));
+    }
+
+    #[test]
+    fn lines_have_no_gaps() {
+        assert_eq!(vec![(2, 2)], line((2, 2), (2, 2)));
+
+        for to in [(7, 3), (-4, 9), (0, -6), (-5, -5)] {
+            let cells = line((0, 0), to);
+            assert_eq!((0, 0), cells[0]);
+            assert_eq!(to, *cells.last().unwrap());
+            assert_eq!(to.0.abs().max(to.1.abs()) as usize + 1, cells.len());
+            for pair in cells.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                assert!((a.0 - b.0).abs() <= 1 && (a.1 - b.1).abs() <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn rectangles_and_ellipses() {
+        assert_eq!(12, rectangle((0, 0), (3, 3), false).len());
+        assert_eq!(16, rectangle((3, 3), (0, 0), true).len());
+
+        let filled = ellipse((-5, -3), (5, 3), true);
+        let outline = ellipse((-5, -3), (5, 3), false);
+        assert!(filled.contains(&(0, 0)) && !outline.contains(&(0, 0)));
+        assert!(outline.contains(&(-5, 0)) && outline.contains(&(5, 0)));
+        assert!(outline.contains(&(0, 3)) && outline.contains(&(0, -3)));
+        assert!(outline.iter().all(|cell| filled.contains(cell)));
+    }
+
+    #[test]
+    fn flood_fill_is_bounded() {
+        let walls = rectangle((0, 0), (4, 4), false);
+        let inside = flood_fill((2, 2), 100, |pos| !walls.contains(&pos)).unwrap();
+        assert_eq!(9, inside.len());
+
+        assert_eq!(None, flood_fill((10, 10), 100, |pos| !walls.contains(&pos)));
+    }
+}
@@ -0,0 +1,51 @@
+// simulation.rs
+use crate::resources::{SimulateWorldState, SimulationTick};
+use bevy::{ecs::schedule::ScheduleLabel, prelude::*};
+
+/// One simulation tick. Run from `FixedUpdate` by [`run_simulation`], so the
+/// world advances at `SimulateWorldState::tick_rate` (scaled by its speed)
+/// regardless of frame rate.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Simulation;
+
+/// Phases of a tick, executed in declaration order.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SimulationSet {
+    Activity,
+    Physics,
+    Reactions,
+    Heat,
+    Combustion,
+    Tick,
+}
+
+pub fn run_simulation(world: &mut World) {
+    let ticks = world
+        .resource_mut::<SimulateWorldState>()
+        .bypass_change_detection()
+        .take_ticks();
+
+    for _ in 0..ticks {
+        world.run_schedule(Simulation);
+    }
+}
+
+pub fn advance_tick(mut tick: ResMut<SimulationTick>) {
+    tick.tick += 1;
+}
+
+pub fn apply_tick_rate(state: Res<SimulateWorldState>, mut time: ResMut<Time<Fixed>>) {
+    if state.is_changed() {
+        // The inspector can set any value, and a zero or non-finite rate
+        // would make the timestep panic.
+        let tick_rate = if state.tick_rate.is_nan() {
+            SimulateWorldState::MIN_TICK_RATE
+        } else {
+            state.tick_rate.clamp(
+                SimulateWorldState::MIN_TICK_RATE,
+                SimulateWorldState::MAX_TICK_RATE,
+            )
+        };
+        time.set_timestep_hz(tick_rate);
+    }
+}
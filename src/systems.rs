@@ -1,6 +1,13 @@
 pub mod camera;
 pub mod cell_management;
+pub mod combustion;
+pub mod heat;
+pub mod history;
 pub mod input_handling;
 pub mod physics;
+pub mod reactions;
+pub mod render;
+pub mod tools;
 pub mod ui_systems;
 pub mod window_management;
+pub mod world_io;
@@ -11,7 +11,11 @@ pub fn move_camera(
     mut camera_q: Query<&mut Transform, With<MainCamera>>,
     keys: Res<Input<KeyCode>>,
     time: Res<Time>,
+    state: Res<EguiHoverState>,
 ) {
+    if state.is_typing {
+        return;
+    }
     let mut camera_transform = camera_q.single_mut();
     let mut move_dir = Vec2::new(0f32, 0f32);
     let speed = 250;
@@ -1,126 +1,97 @@
-use crate::components::{Cell, MainCamera};
-use crate::enums::{CellPhysicsType, CellType, CELL_SIZE};
-use crate::events::{RemoveCellEvent, SpawnCellEvent};
+use crate::components::MainCamera;
+use crate::events::{CellEditEvents, RemoveCellEvent, SpawnCellEvent};
+use crate::materials::MaterialRegistry;
 use crate::resources::cell_world::CellWorld;
-use crate::resources::{CellMesh, CellTypeToSpawn, CursorPosition, EguiHoverState, SandMaterials};
-use crate::utils::{align_to_grid, position_to_cell_coords};
-use bevy::prelude::*;
-use bevy::sprite::MaterialMesh2dBundle;
+use crate::resources::edit_history::EditHistory;
+use crate::resources::{Brush, CellTypeToSpawn, CursorPosition, EguiHoverState};
+use crate::utils::position_to_cell_coords;
+use bevy::{prelude::*, utils::HashMap};
 
+/// Paints with the left button and erases with the right, stamping the brush
+/// along the line from the previous frame's cursor cell.
 pub fn spawn_or_remove_cell_on_click(
     buttons: Res<Input<MouseButton>>,
     cursor_position: Res<CursorPosition>,
-    mut ev_spawn_cell: EventWriter<SpawnCellEvent>,
-    mut ev_remove_cell: EventWriter<RemoveCellEvent>,
+    mut edits: CellEditEvents,
     state: ResMut<EguiHoverState>,
     cell_type_to_spawn: Res<CellTypeToSpawn>,
+    brush: Res<Brush>,
+    mut last_cell: Local<Option<(isize, isize)>>,
 ) {
-    if buttons.pressed(MouseButton::Left) && !state.is_hovered {
-        ev_spawn_cell.send(SpawnCellEvent {
-            pos: cursor_position.pos,
-            cell_type: cell_type_to_spawn.type_to_select,
-        });
-    } else if buttons.pressed(MouseButton::Right) && !state.is_hovered {
-        ev_remove_cell.send(RemoveCellEvent {pos: position_to_cell_coords(cursor_position.pos)});
+    let painting = buttons.pressed(MouseButton::Left);
+    let erasing = buttons.pressed(MouseButton::Right);
+    if state.is_hovered || !(painting || erasing) {
+        *last_cell = None;
+        return;
+    }
+
+    let cell = position_to_cell_coords(cursor_position.pos);
+    let cells = brush.stroke(last_cell.unwrap_or(cell), cell);
+    *last_cell = Some(cell);
+
+    if painting {
+        edits.paint(cells, cell_type_to_spawn.type_to_select);
+    } else {
+        edits.erase(cells);
     }
 }
 
+/// Paints under every finger, following each drag from its previous cell.
+/// Nothing is painted over the UI, nor by touches that started there.
 pub fn spawn_cell_on_touch(
     touches: Res<Touches>,
     camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
-    mut ev_spawn_cell: EventWriter<SpawnCellEvent>,
+    mut edits: CellEditEvents,
+    state: Res<EguiHoverState>,
     cell_type_to_spawn: Res<CellTypeToSpawn>,
+    brush: Res<Brush>,
+    mut last_cells: Local<HashMap<u64, Option<(isize, isize)>>>,
 ) {
     let (camera, camera_transform) = camera_q.single();
+    last_cells.retain(|&id, _| touches.get_pressed(id).is_some());
 
     for finger in touches.iter() {
-        if touches.just_pressed(finger.id()) {
-            let touch_position = finger.position();
-            let mut new_touch_position = camera
-                .viewport_to_world_2d(camera_transform, touch_position)
-                .unwrap();
-            new_touch_position = align_to_grid(new_touch_position);
+        let Some(touch_position) = camera.viewport_to_world_2d(camera_transform, finger.position())
+        else {
+            continue;
+        };
+        let cell = position_to_cell_coords(touch_position);
+        let Some(last_cell) = last_cells
+            .entry(finger.id())
+            .or_insert_with(|| (!state.is_hovered).then_some(cell))
+        else {
+            continue;
+        };
+        let from = std::mem::replace(last_cell, cell);
 
-            ev_spawn_cell.send(SpawnCellEvent {
-                pos: new_touch_position,
-                cell_type: cell_type_to_spawn.type_to_select,
-            });
-            return;
+        if !state.is_hovered {
+            edits.paint(brush.stroke(from, cell), cell_type_to_spawn.type_to_select);
         }
     }
 }
 
 pub fn spawn_cell(
-    mut commands: Commands,
-    materials: Res<SandMaterials>,
-    cell_mesh: Res<CellMesh>,
     mut cell_world: ResMut<CellWorld>,
     mut ev_spawn_cell: EventReader<SpawnCellEvent>,
+    materials: Res<MaterialRegistry>,
+    mut history: ResMut<EditHistory>,
 ) {
     for ev in ev_spawn_cell.read() {
-        let grid_pos = position_to_cell_coords(ev.pos);
-        if cell_world.is_cell_empty(grid_pos) {
-            if let Some(&material_index) = materials.color_ids.get(&ev.cell_type) {
-                // Access the material using the material index
-                if let Some(material) = materials.materials.get(material_index) {
-                    cell_world.insert(
-                        grid_pos.0,
-                        grid_pos.1,
-                        Some(
-                            commands
-                                .spawn((
-                                    MaterialMesh2dBundle {
-                                        mesh: cell_mesh.mesh.clone(),
-                                        transform: Transform {
-                                            translation: ev.pos.extend(0.0),
-                                            scale: CELL_SIZE,
-                                            ..Default::default()
-                                        },
-                                        material: material.clone(),
-                                        ..Default::default()
-                                    },
-                                    Cell {
-                                        cell_type: ev.cell_type,
-                                    },
-                                    get_physics_component(ev.cell_type),
-                                ))
-                                .id(),
-                        ),
-                    );
-                } else {
-                    warn!("Material for cell type {:?} not found", ev.cell_type);
-                }
-            } else {
-                warn!("No material index for cell type {:?}", ev.cell_type);
-            }
+        if cell_world.is_cell_empty(ev.pos) {
+            let cell = materials.new_cell(ev.material);
+            history.set(&mut cell_world, ev.pos, Some(cell));
         }
     }
 }
 
 pub fn remove_cell(
     mut ev_remove_cell: EventReader<RemoveCellEvent>,
-    mut commands: Commands,
     mut world: ResMut<CellWorld>,
+    mut history: ResMut<EditHistory>,
 ) {
     for event in ev_remove_cell.read() {
-        let entity = world.get(event.pos.0, event.pos.1);
-        match entity {
-            Some(entity) => {
-                world.insert(event.pos.0, event.pos.1, None);
-                commands.entity(entity).despawn();
-            }
-            None => {
-                return;
-            }
+        if !world.is_cell_empty(event.pos) {
+            history.set(&mut world, event.pos, None);
         }
     }
 }
-
-pub fn get_physics_component(cell: CellType) -> CellPhysicsType {
-    match cell {
-        CellType::Sand => CellPhysicsType::Sand,
-        CellType::Stone => CellPhysicsType::Static,
-        CellType::BloodStone => CellPhysicsType::BloodStone,
-        CellType::Blood => CellPhysicsType::Fluid,
-    }
-}
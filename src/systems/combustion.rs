@@ -0,0 +1,176 @@
+use crate::{
+    materials::{MaterialId, MaterialRegistry},
+    resources::{
+        cell_world::{Cell, CellWorld},
+        Gravity, GravityFrame, SimulationRng,
+    },
+};
+use bevy::prelude::*;
+
+/// Burning cells are held at least this hot, so heat conduction carries the
+/// fire to neighbours that are not flammable enough to catch it directly.
+const FLAME_TEMPERATURE: f32 = 800.0;
+
+/// Chance per tick that a burning cell puts a flame into the empty cell
+/// above it, against gravity.
+const FLAME_CHANCE: f32 = 0.3;
+
+pub fn burn_cells(
+    mut cell_world: ResMut<CellWorld>,
+    materials: Res<MaterialRegistry>,
+    gravity: Res<Gravity>,
+    mut rng: ResMut<SimulationRng>,
+) {
+    step_combustion(&mut cell_world, &materials, gravity.frame(), &mut rng.rng);
+}
+
+/// Ignites cells that are hot enough, spreads fire from burning cells to
+/// flammable neighbours and burns cells out. Cells ignited this tick start
+/// spreading on the next one. Flames rise straight up without gravity.
+pub fn step_combustion(
+    cell_world: &mut CellWorld,
+    materials: &MaterialRegistry,
+    gravity: Option<GravityFrame>,
+    rng: &mut fastrand::Rng,
+) {
+    let fire = materials.id("Fire");
+    let up = gravity.map_or((0, 1), |frame| frame.step(4));
+
+    let mut burning = Vec::new();
+    let mut igniting = Vec::new();
+    for chunk_pos in cell_world.active_chunks() {
+        for (x, y) in CellWorld::chunk_cell_coords(chunk_pos) {
+            let Some(cell) = cell_world.get(x, y) else {
+                continue;
+            };
+            if cell.flags & Cell::BURNING != 0 {
+                burning.push((x, y));
+            } else if materials
+                .get(cell.material)
+                .ignition_temperature
+                .is_some_and(|ignition| cell.temperature >= ignition)
+            {
+                igniting.push((x, y));
+            }
+        }
+    }
+
+    for (x, y) in burning {
+        burn(cell_world, materials, rng, fire, up, x, y);
+    }
+    for (x, y) in igniting {
+        ignite(cell_world, x, y);
+    }
+}
+
+fn burn(
+    cell_world: &mut CellWorld,
+    materials: &MaterialRegistry,
+    rng: &mut fastrand::Rng,
+    fire: Option<MaterialId>,
+    up: (isize, isize),
+    x: isize,
+    y: isize,
+) {
+    let Some(mut cell) = cell_world.get(x, y) else {
+        return;
+    };
+    let material = materials.get(cell.material);
+
+    cell.age = cell.age.saturating_add(1);
+    if cell.age >= material.burn_time {
+        let remains = material
+            .burns_into
+            .map(|remains| materials.new_cell_with(remains, rng));
+        cell_world.insert(x, y, remains);
+        return;
+    }
+    cell.temperature = cell.temperature.max(FLAME_TEMPERATURE);
+    cell_world.insert(x, y, Some(cell));
+
+    for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+        let Some(neighbour) = cell_world.get(nx, ny) else {
+            continue;
+        };
+        if neighbour.flags & Cell::BURNING == 0
+            && rng.f32() < materials.get(neighbour.material).flammability
+        {
+            ignite(cell_world, nx, ny);
+        }
+    }
+
+    if let Some(fire) = fire.filter(|&fire| fire != cell.material) {
+        let (fx, fy) = (x + up.0, y + up.1);
+        if cell_world.is_cell_empty((fx, fy)) && rng.f32() < FLAME_CHANCE {
+            cell_world.insert(fx, fy, Some(materials.new_cell_with(fire, rng)));
+        }
+    }
+}
+
+fn ignite(cell_world: &mut CellWorld, x: isize, y: isize) {
+    let Some(mut cell) = cell_world.get(x, y) else {
+        return;
+    };
+    cell.flags |= Cell::BURNING;
+    cell.age = 0;
+    cell_world.insert(x, y, Some(cell));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::step_combustion;
+    use crate::{
+        materials::MaterialRegistry,
+        resources::{
+            cell_world::{Cell, CellWorld},
+            Gravity,
+        },
+    };
+
+    #[test]
+    fn hot_wood_burns_to_ash() {
+        let materials = MaterialRegistry::default();
+        let wood = materials.id("Wood").unwrap();
+        let mut rng = fastrand::Rng::with_seed(1);
+        let mut world = CellWorld::default();
+        let mut cell = materials.new_cell(wood);
+        cell.temperature = 400.0;
+        world.insert(0, 0, Some(cell));
+
+        world.advance_activity();
+        step_combustion(&mut world, &materials, None, &mut rng);
+        assert_ne!(0, world.get(0, 0).unwrap().flags & Cell::BURNING);
+
+        for _ in 0..materials.get(wood).burn_time {
+            world.advance_activity();
+            step_combustion(&mut world, &materials, None, &mut rng);
+        }
+        assert_eq!(
+            materials.id("Ash"),
+            world.get(0, 0).map(|cell| cell.material)
+        );
+    }
+
+    #[test]
+    fn flames_rise_against_gravity() {
+        let materials = MaterialRegistry::default();
+        let wood = materials.id("Wood").unwrap();
+        let mut rng = fastrand::Rng::with_seed(1);
+        let mut world = CellWorld::default();
+        let mut cell = materials.new_cell(wood);
+        cell.flags |= Cell::BURNING;
+        world.insert(0, 0, Some(cell));
+        let mut gravity = Gravity::default();
+        gravity.set_angle(0.0);
+
+        for _ in 0..10 {
+            world.advance_activity();
+            step_combustion(&mut world, &materials, gravity.frame(), &mut rng);
+        }
+        assert_eq!(
+            materials.id("Fire"),
+            world.get(-1, 0).map(|cell| cell.material)
+        );
+        assert!(world.is_cell_empty((0, 1)));
+    }
+}
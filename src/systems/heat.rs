@@ -0,0 +1,107 @@
+use crate::{
+    materials::MaterialRegistry,
+    resources::{
+        cell_world::{CellWorld, AMBIENT_TEMPERATURE},
+        SimulationRng,
+    },
+};
+use bevy::prelude::*;
+
+/// Conductivity between a cell and an empty neighbour, which is treated as
+/// air at ambient temperature.
+const AIR_CONDUCTIVITY: f32 = 0.001;
+
+/// Smaller changes are dropped so regions near equilibrium stop waking their
+/// chunks.
+const MIN_TEMPERATURE_CHANGE: f32 = 0.01;
+
+pub fn conduct_heat(
+    mut cell_world: ResMut<CellWorld>,
+    materials: Res<MaterialRegistry>,
+    mut rng: ResMut<SimulationRng>,
+) {
+    step_heat(&mut cell_world, &materials, &mut rng.rng);
+}
+
+/// Exchanges heat between neighbouring cells in the active chunks, then
+/// applies any phase change the new temperatures trigger. All temperatures
+/// are computed from the same snapshot so the scan order does not matter.
+pub fn step_heat(
+    cell_world: &mut CellWorld,
+    materials: &MaterialRegistry,
+    rng: &mut fastrand::Rng,
+) {
+    let mut changes = Vec::new();
+    for chunk_pos in cell_world.active_chunks() {
+        for (x, y) in CellWorld::chunk_cell_coords(chunk_pos) {
+            let Some(cell) = cell_world.get(x, y) else {
+                continue;
+            };
+            let conductivity = materials.get(cell.material).conductivity;
+
+            let mut delta = 0.0;
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                let (temperature, other_conductivity) = match cell_world.get(nx, ny) {
+                    Some(other) => (
+                        other.temperature,
+                        materials.get(other.material).conductivity,
+                    ),
+                    None => (AMBIENT_TEMPERATURE, AIR_CONDUCTIVITY),
+                };
+                delta +=
+                    conductivity.min(other_conductivity) * (temperature - cell.temperature) / 4.0;
+            }
+
+            if delta.abs() >= MIN_TEMPERATURE_CHANGE {
+                changes.push(((x, y), cell.temperature + delta));
+            }
+        }
+    }
+
+    for ((x, y), temperature) in changes {
+        let Some(mut cell) = cell_world.get(x, y) else {
+            continue;
+        };
+        cell.temperature = temperature;
+
+        let material = materials.get(cell.material);
+        let phase_change = material
+            .above
+            .filter(|phase| temperature > phase.temperature)
+            .or(material
+                .below
+                .filter(|phase| temperature < phase.temperature));
+        if let Some(phase_change) = phase_change {
+            cell.material = phase_change.into;
+            cell.shade = rng.u8(..);
+            cell.age = 0;
+        }
+
+        cell_world.insert(x, y, Some(cell));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::step_heat;
+    use crate::{materials::MaterialRegistry, resources::cell_world::CellWorld};
+
+    #[test]
+    fn water_next_to_lava_boils() {
+        let materials = MaterialRegistry::default();
+        let water = materials.id("Water").unwrap();
+        let lava = materials.id("Lava").unwrap();
+        let mut world = CellWorld::default();
+        world.insert(0, 0, Some(materials.new_cell(lava)));
+        world.insert(1, 0, Some(materials.new_cell(water)));
+        world.advance_activity();
+
+        step_heat(&mut world, &materials, &mut fastrand::Rng::with_seed(1));
+
+        assert_eq!(
+            materials.id("Steam"),
+            world.get(1, 0).map(|cell| cell.material)
+        );
+        assert!(world.get(0, 0).unwrap().temperature < materials.get(lava).temperature);
+    }
+}
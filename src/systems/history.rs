@@ -0,0 +1,66 @@
+use crate::{
+    events::{ClearWorldEvent, RedoEvent, UndoEvent},
+    resources::{cell_world::CellWorld, edit_history::EditHistory},
+};
+use bevy::prelude::*;
+
+/// Phases of user edits within a frame, executed in declaration order. Queued
+/// cell events are applied, and a finished stroke committed, before anything
+/// writes to the world through the history directly, so two edits never end
+/// up interleaved in one step or applied out of order.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EditSet {
+    /// Turns input into `SpawnCellEvent`s and `RemoveCellEvent`s.
+    Queue,
+    /// Applies the queued events to the pending edit.
+    Apply,
+    /// Undo, redo, clearing, pasting, cutting and loading.
+    Direct,
+}
+
+/// Closes the pending edit once every mouse button and finger is released,
+/// so a whole stroke is undone at once.
+pub fn finish_edit(
+    buttons: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    mut history: ResMut<EditHistory>,
+) {
+    if buttons.get_pressed().next().is_none() && touches.iter().next().is_none() {
+        history.commit();
+    }
+}
+
+pub fn undo_or_redo(
+    mut ev_undo: EventReader<UndoEvent>,
+    mut ev_redo: EventReader<RedoEvent>,
+    mut history: ResMut<EditHistory>,
+    mut cell_world: ResMut<CellWorld>,
+) {
+    for _ in ev_undo.read() {
+        if !history.undo(&mut cell_world) {
+            info!("Nothing to undo");
+        }
+    }
+    for _ in ev_redo.read() {
+        if !history.redo(&mut cell_world) {
+            info!("Nothing to redo");
+        }
+    }
+}
+
+pub fn clear_world(
+    mut ev_clear_world: EventReader<ClearWorldEvent>,
+    mut history: ResMut<EditHistory>,
+    mut cell_world: ResMut<CellWorld>,
+) {
+    if ev_clear_world.read().last().is_none() {
+        return;
+    }
+
+    history.commit();
+    let positions: Vec<_> = cell_world.iter().map(|(pos, _)| pos).collect();
+    for pos in positions {
+        history.set(&mut cell_world, pos, None);
+    }
+    history.commit();
+}
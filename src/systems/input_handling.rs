@@ -1 +1,130 @@
+use crate::enums::Tool;
+use crate::events::{CopySelectionEvent, RedoEvent, UndoEvent};
+use crate::resources::clipboard::Clipboard;
+use crate::resources::{Brush, EguiHoverState, SimulateWorldState, ToolState};
+use bevy::{ecs::system::SystemParam, prelude::*};
 
+/// Mouse buttons, with fingers on a touchscreen acting as the left button, so
+/// click-driven tools work the same with either.
+#[derive(SystemParam)]
+pub struct PointerButtons<'w> {
+    buttons: Res<'w, Input<MouseButton>>,
+    touches: Res<'w, Touches>,
+}
+
+impl PointerButtons<'_> {
+    pub fn just_pressed(&self, button: MouseButton) -> bool {
+        self.buttons.just_pressed(button)
+            || button == MouseButton::Left && self.touches.any_just_pressed()
+    }
+
+    pub fn just_released(&self, button: MouseButton) -> bool {
+        self.buttons.just_released(button)
+            || button == MouseButton::Left && self.touches.any_just_released()
+    }
+
+    pub fn any_just_pressed(&self, buttons: impl IntoIterator<Item = MouseButton>) -> bool {
+        buttons.into_iter().any(|button| self.just_pressed(button))
+    }
+}
+
+/// Space pauses, `.` advances one tick, `-` and `=` halve and double the
+/// simulation speed.
+pub fn simulation_shortcuts(
+    keys: Res<Input<KeyCode>>,
+    mut state: ResMut<SimulateWorldState>,
+    egui_state: Res<EguiHoverState>,
+) {
+    if egui_state.is_typing {
+        return;
+    }
+    if keys.just_pressed(KeyCode::Space) {
+        state.is_simulating = !state.is_simulating;
+    }
+    if keys.just_pressed(KeyCode::Period) {
+        state.pending_ticks += 1;
+    }
+    if keys.just_pressed(KeyCode::Minus) {
+        state.speed =
+            (state.speed / 2.0).clamp(SimulateWorldState::MIN_SPEED, SimulateWorldState::MAX_SPEED);
+    }
+    if keys.just_pressed(KeyCode::Equals) {
+        state.speed =
+            (state.speed * 2.0).clamp(SimulateWorldState::MIN_SPEED, SimulateWorldState::MAX_SPEED);
+    }
+}
+
+/// `[` and `]` shrink and grow the brush.
+pub fn brush_shortcuts(
+    keys: Res<Input<KeyCode>>,
+    mut brush: ResMut<Brush>,
+    egui_state: Res<EguiHoverState>,
+) {
+    if egui_state.is_typing {
+        return;
+    }
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        brush.radius = brush.radius.saturating_sub(1);
+    }
+    if keys.just_pressed(KeyCode::BracketRight) {
+        brush.radius = (brush.radius + 1).min(Brush::MAX_RADIUS);
+    }
+}
+
+/// Ctrl+Z undoes the latest edit, Ctrl+Y or Ctrl+Shift+Z redoes it.
+pub fn history_shortcuts(
+    keys: Res<Input<KeyCode>>,
+    mut ev_undo: EventWriter<UndoEvent>,
+    mut ev_redo: EventWriter<RedoEvent>,
+    egui_state: Res<EguiHoverState>,
+) {
+    if egui_state.is_typing || !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if keys.just_pressed(KeyCode::Z) && !shift {
+        ev_undo.send(UndoEvent);
+    }
+    if keys.just_pressed(KeyCode::Y) || keys.just_pressed(KeyCode::Z) && shift {
+        ev_redo.send(RedoEvent);
+    }
+}
+
+/// Ctrl+C and Ctrl+X copy and cut the selection, Ctrl+V picks the paste tool.
+/// While pasting, R rotates and M mirrors the clipboard and Escape goes back
+/// to selecting.
+pub fn clipboard_shortcuts(
+    keys: Res<Input<KeyCode>>,
+    mut tools: ResMut<ToolState>,
+    mut clipboard: ResMut<Clipboard>,
+    mut ev_copy_selection: EventWriter<CopySelectionEvent>,
+    egui_state: Res<EguiHoverState>,
+) {
+    if egui_state.is_typing {
+        return;
+    }
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        if keys.just_pressed(KeyCode::C) {
+            ev_copy_selection.send(CopySelectionEvent { cut: false });
+        }
+        if keys.just_pressed(KeyCode::X) {
+            ev_copy_selection.send(CopySelectionEvent { cut: true });
+        }
+        if keys.just_pressed(KeyCode::V) && !clipboard.is_empty() {
+            tools.tool = Tool::Paste;
+        }
+        return;
+    }
+    if tools.tool != Tool::Paste {
+        return;
+    }
+    if keys.just_pressed(KeyCode::R) {
+        clipboard.rotate();
+    }
+    if keys.just_pressed(KeyCode::M) {
+        clipboard.mirror();
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        tools.tool = Tool::Select;
+    }
+}
@@ -1,118 +1,706 @@
-use crate::enums::cell_physics_type_filters;
 use crate::{
-    enums::{CellType, CELL_SIZE},
-    events::SpawnCellEvent,
-    resources::{cell_world::CellWorld, SimulateWorldState},
-    utils::position_to_cell_coords,
+    enums::CellPhysicsType,
+    materials::MaterialRegistry,
+    resources::{
+        cell_world::CellWorld, Gravity, GravityFrame, SimulateWorldState, SimulationRng,
+        SimulationTick, GRID_DIRECTIONS,
+    },
 };
 use bevy::prelude::*;
-use bevy_enum_filter::Enum;
 
-pub fn sand_physics(
-    mut query: Query<(Entity, &mut Transform), With<Enum!(CellPhysicsType::Sand)>>,
+pub fn advance_cell_world_activity(mut cell_world: ResMut<CellWorld>) {
+    cell_world.advance_activity();
+}
+
+/// Sleeping chunks would ignore a new gravity, so wake them all.
+pub fn wake_on_gravity_change(gravity: Res<Gravity>, mut cell_world: ResMut<CellWorld>) {
+    if gravity.is_changed() {
+        cell_world.wake_all();
+    }
+}
+
+pub fn simulate_cells(
     mut cell_world: ResMut<CellWorld>,
+    materials: Res<MaterialRegistry>,
+    tick: Res<SimulationTick>,
     state: Res<SimulateWorldState>,
+    gravity: Res<Gravity>,
+    mut rng: ResMut<SimulationRng>,
 ) {
-    if !state.is_simulating {
-        return;
+    step_cells(
+        &mut cell_world,
+        &materials,
+        gravity.frame(),
+        &mut rng.rng,
+        (tick.tick, state.tick_rate),
+    );
+}
+
+/// Steps every cell in the active chunks once, in [`update_order`].
+pub fn step_cells(
+    cell_world: &mut CellWorld,
+    materials: &MaterialRegistry,
+    gravity: Option<GravityFrame>,
+    rng: &mut fastrand::Rng,
+    emission: (u64, f64),
+) {
+    for (x, y) in update_order(cell_world, gravity, emission.0) {
+        let Some(cell) = cell_world.get(x, y) else {
+            continue;
+        };
+        if cell_world.is_updated(&cell) {
+            continue;
+        }
+        cell_world.mark_updated(x, y);
+
+        match materials.get(cell.material).physics {
+            CellPhysicsType::Sand => sand_physics(cell_world, materials, gravity, rng, x, y),
+            CellPhysicsType::Fluid => fluid_physics(cell_world, materials, gravity, rng, x, y),
+            CellPhysicsType::Gas => gas_physics(cell_world, materials, gravity, rng, x, y),
+            CellPhysicsType::Emitter => emitter_physics(cell_world, materials, rng, emission, x, y),
+            CellPhysicsType::Sink => sink_physics(cell_world, materials, x, y),
+            CellPhysicsType::Static => {}
+        }
     }
+}
+
+/// Occupied cells of the active chunks, furthest along gravity first. Each
+/// layer is swept sideways in a direction that flips every tick, so neither
+/// side of a pile is favoured.
+fn update_order(
+    cell_world: &CellWorld,
+    gravity: Option<GravityFrame>,
+    tick: u64,
+) -> Vec<(isize, isize)> {
+    let (down, across) = gravity.map_or(((0, -1), (1, 0)), |gravity| {
+        (gravity.step(0), gravity.step(2))
+    });
+    let sweep = if tick & 1 == 0 { 1 } else { -1 };
+
+    let mut positions: Vec<_> = cell_world
+        .active_chunks()
+        .into_iter()
+        .flat_map(CellWorld::chunk_cell_coords)
+        .filter(|&pos| !cell_world.is_cell_empty(pos))
+        .collect();
+    positions.sort_unstable_by_key(|&(x, y)| {
+        (
+            -(x * down.0 + y * down.1),
+            sweep * (x * across.0 + y * across.1),
+        )
+    });
+    positions
+}
+
+/// Whether a cell of the given density can move into `pos`: the slot is
+/// empty, or holds a lighter liquid the cell can trade places with.
+fn can_move_into(
+    cell_world: &CellWorld,
+    materials: &MaterialRegistry,
+    density: f32,
+    pos: (isize, isize),
+) -> bool {
+    match cell_world.get(pos.0, pos.1) {
+        None => true,
+        Some(other) => {
+            let other = materials.get(other.material);
+            other.physics.is_displaceable() && other.density < density
+        }
+    }
+}
+
+const MAX_FALL_SPEED: f32 = 8.0;
+
+/// Share of the fall speed turned into sideways speed when a cell lands.
+const SPLASH: f32 = 0.5;
+
+/// Share of sideways speed kept for each cell slid along the ground.
+const FRICTION: f32 = 0.7;
+
+/// `pos` moved `times` grid steps of `step`.
+fn offset(pos: (isize, isize), step: (isize, isize), times: isize) -> (isize, isize) {
+    (pos.0 + step.0 * times, pos.1 + step.1 * times)
+}
 
-    for (entity, mut transform) in query.iter_mut() {
-        let below_x = (transform.translation.x / CELL_SIZE.x).floor() as isize;
-        let below_y = ((transform.translation.y - CELL_SIZE.y) / CELL_SIZE.y).floor() as isize;
-        if cell_world.get(below_x, below_y).is_none() {
-            transform.translation.y -= CELL_SIZE.y;
+/// Accelerates the cell along gravity and moves it along its path, sinking
+/// through anything lighter and stopping at the first obstruction. Landing
+/// turns part of the fall speed sideways; once grounded the cell slides
+/// diagonally down or along the ground while it has sideways speed. Returns
+/// whether the cell moved.
+fn fall(
+    cell_world: &mut CellWorld,
+    materials: &MaterialRegistry,
+    gravity: GravityFrame,
+    rng: &mut fastrand::Rng,
+    x: isize,
+    y: isize,
+) -> bool {
+    let Some(mut cell) = cell_world.get(x, y) else {
+        return false;
+    };
+    let density = materials.get(cell.material).density;
+    let can_move = |pos| can_move_into(cell_world, materials, density, pos);
 
-            cell_world.insert(below_x, below_y, Some(entity));
-            cell_world.insert(below_x, below_y + 1, None);
-        } else if cell_world.get(below_x - 1, below_y).is_none()
-            && !cell_world.get(below_x - 1, below_y + 1).is_some()
-        {
-            transform.translation.y -= CELL_SIZE.y;
-            transform.translation.x -= CELL_SIZE.x;
+    let down = gravity.step(0);
+    let along = gravity.unit(0);
+    let across = gravity.unit(2);
+    let mut speed = cell.velocity.dot(along).max(0.0);
+    let mut lateral = cell.velocity.dot(across);
 
-            cell_world.insert(below_x - 1, below_y, Some(entity));
-            cell_world.insert(below_x, below_y + 1, None);
-        } else if cell_world.get(below_x + 1, below_y).is_none()
-            && !cell_world.get(below_x + 1, below_y + 1).is_some()
-        {
-            transform.translation.y -= CELL_SIZE.y;
-            transform.translation.x += CELL_SIZE.x;
+    if can_move(offset((x, y), down, 1)) {
+        speed = (speed + gravity.strength).min(MAX_FALL_SPEED);
+        let steps = speed.ceil() as isize;
+        let mut distance = 1;
+        while distance < steps && can_move(offset((x, y), down, distance + 1)) {
+            distance += 1;
+        }
+        if distance < steps {
+            let side = if lateral != 0.0 {
+                lateral.signum()
+            } else if rng.bool() {
+                -1.0
+            } else {
+                1.0
+            };
+            lateral = side * speed * SPLASH;
+            speed = 0.0;
+        }
 
-            cell_world.insert(below_x + 1, below_y, Some(entity));
-            cell_world.insert(below_x, below_y + 1, None);
+        cell.velocity = along * speed + across * lateral;
+        cell_world.insert(x, y, Some(cell));
+        for step in 0..distance {
+            cell_world.swap(offset((x, y), down, step), offset((x, y), down, step + 1));
         }
+        return true;
     }
+
+    // Diagonal moves must not squeeze between two cells, which only matters
+    // when "down" is a straight grid direction.
+    let slip = |side: isize| {
+        let target = offset((x, y), gravity.step(side), 1);
+        let squeezed =
+            !gravity.is_diagonal() && !can_move(offset((x, y), gravity.step(2 * side), 1));
+        (can_move(target) && !squeezed).then_some(target)
+    };
+    let side = if lateral > 0.0 {
+        1
+    } else if lateral < 0.0 || rng.bool() {
+        -1
+    } else {
+        1
+    };
+    let slide = offset((x, y), gravity.step(2 * side), 1);
+    let target = if let Some(target) = slip(side).or_else(|| slip(-side)) {
+        Some(target)
+    } else if lateral.abs() >= 1.0 && can_move(slide) {
+        lateral *= FRICTION;
+        Some(slide)
+    } else {
+        None
+    };
+
+    let Some(target) = target else {
+        if cell.velocity != Vec2::ZERO {
+            cell.velocity = Vec2::ZERO;
+            cell_world.insert(x, y, Some(cell));
+        }
+        return false;
+    };
+
+    cell.velocity = across * lateral;
+    cell_world.insert(x, y, Some(cell));
+    cell_world.swap((x, y), target);
+    true
 }
 
-pub fn fluid_physics(
-    mut query: Query<(Entity, &mut Transform), With<Enum!(CellPhysicsType::Fluid)>>,
-    mut cell_world: ResMut<CellWorld>,
-    state: Res<SimulateWorldState>,
+fn sand_physics(
+    cell_world: &mut CellWorld,
+    materials: &MaterialRegistry,
+    gravity: Option<GravityFrame>,
+    rng: &mut fastrand::Rng,
+    x: isize,
+    y: isize,
 ) {
-    if !state.is_simulating {
-        return;
+    if let Some(gravity) = gravity {
+        fall(cell_world, materials, gravity, rng, x, y);
     }
+}
 
-    for (entity, mut transform) in query.iter_mut() {
-        let below_x = (transform.translation.x / CELL_SIZE.x).floor() as isize;
-        let below_y = ((transform.translation.y - CELL_SIZE.y) / CELL_SIZE.y).floor() as isize;
-        if cell_world.get(below_x, below_y).is_none() {
-            transform.translation.y -= CELL_SIZE.y;
+/// Falls like sand, then flows sideways relative to gravity. Liquids hold
+/// still without gravity.
+fn fluid_physics(
+    cell_world: &mut CellWorld,
+    materials: &MaterialRegistry,
+    gravity: Option<GravityFrame>,
+    rng: &mut fastrand::Rng,
+    x: isize,
+    y: isize,
+) {
+    let Some(gravity) = gravity else {
+        return;
+    };
+    if fall(cell_world, materials, gravity, rng, x, y) {
+        return;
+    }
 
-            cell_world.insert(below_x, below_y, Some(entity));
-            cell_world.insert(below_x, below_y + 1, None);
-        } else if cell_world.get(below_x - 1, below_y).is_none()
-            && !cell_world.get(below_x - 1, below_y + 1).is_some()
-        {
-            transform.translation.y -= CELL_SIZE.y;
-            transform.translation.x -= CELL_SIZE.x;
+    let Some(cell) = cell_world.get(x, y) else {
+        return;
+    };
+    let material = materials.get(cell.material);
+    let first = if rng.bool() { -2 } else { 2 };
+    let target = [first, -first].into_iter().find_map(|turns| {
+        flow_target(
+            cell_world,
+            (x, y),
+            gravity.step(turns),
+            gravity.step(0),
+            material.dispersion,
+        )
+    });
+    let Some(target) = target else {
+        return;
+    };
+    if rng.f32() < material.viscosity {
+        // Still able to flow, so keep the chunk awake even though nothing
+        // moved.
+        cell_world.wake(x, y);
+        return;
+    }
+    cell_world.swap((x, y), target);
+}
 
-            cell_world.insert(below_x - 1, below_y, Some(entity));
-            cell_world.insert(below_x, below_y + 1, None);
-        } else if cell_world.get(below_x + 1, below_y).is_none()
-            && !cell_world.get(below_x + 1, below_y + 1).is_some()
-        {
-            transform.translation.y -= CELL_SIZE.y;
-            transform.translation.x += CELL_SIZE.x;
+/// Furthest empty cell within `dispersion` steps of `direction`, stopping
+/// early above a gap so liquid pours over edges instead of across them.
+fn flow_target(
+    cell_world: &CellWorld,
+    from: (isize, isize),
+    direction: (isize, isize),
+    down: (isize, isize),
+    dispersion: u8,
+) -> Option<(isize, isize)> {
+    let mut target = None;
+    for step in 1..=dispersion as isize {
+        let pos = offset(from, direction, step);
+        if !cell_world.is_cell_empty(pos) {
+            break;
+        }
+        target = Some(pos);
+        if cell_world.is_cell_empty(offset(pos, down, 1)) {
+            break;
+        }
+    }
+    target
+}
 
-            cell_world.insert(below_x + 1, below_y, Some(entity));
-            cell_world.insert(below_x, below_y + 1, None);
-        } else if cell_world.get(below_x - 1, below_y + 1).is_none() {
-            transform.translation.x -= CELL_SIZE.x;
+/// Drifts against gravity and sideways at random, rising through heavier
+/// gases, and disappears once the material's lifetime is used up. Without
+/// gravity gases wander in any direction.
+fn gas_physics(
+    cell_world: &mut CellWorld,
+    materials: &MaterialRegistry,
+    gravity: Option<GravityFrame>,
+    rng: &mut fastrand::Rng,
+    x: isize,
+    y: isize,
+) {
+    let Some(mut cell) = cell_world.get(x, y) else {
+        return;
+    };
+    let material = materials.get(cell.material);
+    if let Some(lifetime) = material.lifetime {
+        cell.age = cell.age.saturating_add(1);
+        if cell.age >= lifetime {
+            cell_world.insert(x, y, None);
+            return;
+        }
+        let cell = cell_world.updated(cell);
+        cell_world.insert(x, y, Some(cell));
+    }
 
-            cell_world.insert(below_x - 1, below_y + 1, Some(entity));
-            cell_world.insert(below_x, below_y + 1, None);
-        } else if cell_world.get(below_x + 1, below_y + 1).is_none() {
-            transform.translation.x += CELL_SIZE.x;
+    let can_move = |pos: (isize, isize)| match cell_world.get(pos.0, pos.1) {
+        None => true,
+        Some(other) => {
+            let other = materials.get(other.material);
+            other.physics == CellPhysicsType::Gas && other.density > material.density
+        }
+    };
 
-            cell_world.insert(below_x + 1, below_y + 1, Some(entity));
-            cell_world.insert(below_x, below_y + 1, None);
+    let steps = match gravity {
+        Some(gravity) => {
+            let side = if rng.bool() { -2 } else { 2 };
+            [
+                gravity.step(4 + rng.isize(-1..=1)),
+                gravity.step(4),
+                gravity.step(4 + side),
+                gravity.step(4 - side),
+            ]
         }
+        None => [GRID_DIRECTIONS[rng.usize(..GRID_DIRECTIONS.len())]; 4],
+    };
+    let target = steps
+        .into_iter()
+        .map(|step| offset((x, y), step, 1))
+        .find(|&pos| can_move(pos));
+
+    if let Some(target) = target {
+        cell_world.swap((x, y), target);
     }
 }
 
-pub fn blood_stone_physics(
-    mut query: Query<&mut Transform, With<Enum!(CellPhysicsType::BloodStone)>>,
-    cell_world: ResMut<CellWorld>,
-    mut ev_spawn_cell: EventWriter<SpawnCellEvent>,
-    state: Res<SimulateWorldState>,
+/// Puts a cell of the emitted material next to the emitter when its rate is
+/// due and the target is free.
+fn emitter_physics(
+    cell_world: &mut CellWorld,
+    materials: &MaterialRegistry,
+    rng: &mut fastrand::Rng,
+    emission: (u64, f64),
+    x: isize,
+    y: isize,
 ) {
-    if !state.is_simulating {
+    let Some(cell) = cell_world.get(x, y) else {
+        return;
+    };
+    let Some(emitter) = materials.get(cell.material).emitter else {
+        return;
+    };
+    // Emitters act on a schedule, so their chunk must stay awake between
+    // emissions even when nothing around them moves, and while disabled so
+    // that switching them back on takes effect.
+    cell_world.wake(x, y);
+    if !emitter.enabled {
         return;
     }
 
-    for transform in query.iter_mut() {
-        let mut pos = transform.translation;
-        pos.y -= CELL_SIZE.y;
+    let (tick, tick_rate) = emission;
+    let (dx, dy) = emitter.direction.offset();
+    let target = (x + dx, y + dy);
+    if emitter.emits_on(tick, tick_rate) && cell_world.is_cell_empty(target) {
+        let emitted = cell_world.updated(materials.new_cell_with(emitter.material, rng));
+        cell_world.insert(target.0, target.1, Some(emitted));
+    }
+}
 
-        let grid_pos = position_to_cell_coords(pos);
-        if !cell_world.is_cell_empty(grid_pos) {
+/// Deletes loose cells next to the sink.
+fn sink_physics(cell_world: &mut CellWorld, materials: &MaterialRegistry, x: isize, y: isize) {
+    for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+        let Some(neighbour) = cell_world.get(nx, ny) else {
             continue;
+        };
+        if matches!(
+            materials.get(neighbour.material).physics,
+            CellPhysicsType::Sand | CellPhysicsType::Fluid | CellPhysicsType::Gas
+        ) {
+            cell_world.insert(nx, ny, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        emitter_physics, fluid_physics, gas_physics, sand_physics, sink_physics, step_cells,
+    };
+    use crate::{
+        materials::MaterialRegistry,
+        resources::{
+            cell_world::{Cell, CellWorld},
+            Gravity,
+        },
+        systems::heat::step_heat,
+    };
+
+    #[test]
+    fn sand_sinks_through_lighter_liquid() {
+        let materials = MaterialRegistry::default();
+        let mut rng = fastrand::Rng::with_seed(1);
+        let sand = materials.id("Sand").unwrap();
+        let blood = materials.id("Blood").unwrap();
+        let mut world = CellWorld::default();
+        world.insert(0, 1, Some(Cell::new(sand)));
+        world.insert(0, 0, Some(Cell::new(blood)));
+
+        sand_physics(
+            &mut world,
+            &materials,
+            Gravity::default().frame(),
+            &mut rng,
+            0,
+            1,
+        );
+
+        assert_eq!(sand, world.get(0, 0).unwrap().material);
+        assert_eq!(blood, world.get(0, 1).unwrap().material);
+        assert_eq!(2, world.cell_count);
+    }
+
+    #[test]
+    fn liquid_flows_up_to_its_dispersion() {
+        let materials = MaterialRegistry::default();
+        let mut rng = fastrand::Rng::with_seed(1);
+        let water = materials.id("Water").unwrap();
+        let stone = materials.id("Stone").unwrap();
+        let mut world = CellWorld::default();
+        for x in -20..=20 {
+            world.insert(x, 0, Some(Cell::new(stone)));
+        }
+        world.insert(0, 1, Some(Cell::new(water)));
+
+        fluid_physics(
+            &mut world,
+            &materials,
+            Gravity::default().frame(),
+            &mut rng,
+            0,
+            1,
+        );
+
+        let dispersion = materials.get(water).dispersion as isize;
+        assert!(world.is_cell_empty((0, 1)));
+        assert!(
+            world.get(-dispersion, 1).is_some() || world.get(dispersion, 1).is_some(),
+            "water should flow its full dispersion over a flat floor"
+        );
+    }
+
+    #[test]
+    fn gas_rises_and_dissipates() {
+        let materials = MaterialRegistry::default();
+        let mut rng = fastrand::Rng::with_seed(1);
+        let smoke = materials.id("Smoke").unwrap();
+        let lifetime = materials.get(smoke).lifetime.unwrap();
+        let mut world = CellWorld::default();
+        world.insert(0, 0, Some(Cell::new(smoke)));
+
+        gas_physics(
+            &mut world,
+            &materials,
+            Gravity::default().frame(),
+            &mut rng,
+            0,
+            0,
+        );
+        assert!(world.is_cell_empty((0, 0)));
+        let (pos, cell) = world.iter().map(|(pos, cell)| (pos, *cell)).next().unwrap();
+        assert_eq!(1, pos.1);
+        assert_eq!(1, cell.age);
+
+        let mut aged = cell;
+        aged.age = lifetime - 1;
+        world.insert(pos.0, pos.1, Some(aged));
+        gas_physics(
+            &mut world,
+            &materials,
+            Gravity::default().frame(),
+            &mut rng,
+            pos.0,
+            pos.1,
+        );
+        assert_eq!(0, world.cell_count);
+    }
+
+    #[test]
+    fn emitters_feed_sinks() {
+        let materials = MaterialRegistry::default();
+        let faucet = materials.id("Faucet").unwrap();
+        let drain = materials.id("Drain").unwrap();
+        let water = materials.id("Water").unwrap();
+        let mut world = CellWorld::default();
+        world.insert(0, 2, Some(materials.new_cell(faucet)));
+        world.insert(0, 0, Some(materials.new_cell(drain)));
+
+        let mut rng = fastrand::Rng::with_seed(1);
+        emitter_physics(&mut world, &materials, &mut rng, (0, 60.0), 0, 2);
+        assert_eq!(Some(water), world.get(0, 1).map(|cell| cell.material));
+
+        sink_physics(&mut world, &materials, 0, 0);
+        assert!(world.is_cell_empty((0, 1)));
+        assert_eq!(2, world.cell_count);
+    }
+
+    #[test]
+    fn falling_cells_accelerate() {
+        let materials = MaterialRegistry::default();
+        let mut rng = fastrand::Rng::with_seed(1);
+        let sand = materials.id("Sand").unwrap();
+        let mut world = CellWorld::default();
+        world.insert(0, 100, Some(Cell::new(sand)));
+
+        let mut y = 100;
+        for _ in 0..5 {
+            sand_physics(
+                &mut world,
+                &materials,
+                Gravity::default().frame(),
+                &mut rng,
+                0,
+                y,
+            );
+            y = world.iter().next().unwrap().0 .1;
         }
-        ev_spawn_cell.send(SpawnCellEvent {
-            pos: Vec2::new(pos.x, pos.y),
-            cell_type: CellType::Blood,
-        });
+
+        assert!(y < 95, "sand only fell to {y} in five ticks");
+        assert!(world.get(0, y).unwrap().velocity.y < -1.0);
+    }
+
+    #[test]
+    fn sideways_gravity_pulls_sideways() {
+        let materials = MaterialRegistry::default();
+        let mut rng = fastrand::Rng::with_seed(1);
+        let sand = materials.id("Sand").unwrap();
+        let mut world = CellWorld::default();
+        world.insert(0, 0, Some(Cell::new(sand)));
+        let mut gravity = Gravity::default();
+        gravity.set_angle(0.0);
+
+        sand_physics(&mut world, &materials, gravity.frame(), &mut rng, 0, 0);
+        assert!(world.get(1, 0).is_some());
+
+        gravity.magnitude = 0.0;
+        sand_physics(&mut world, &materials, gravity.frame(), &mut rng, 1, 0);
+        assert!(world.get(1, 0).is_some());
+    }
+
+    #[test]
+    fn seeded_runs_are_deterministic() {
+        let materials = MaterialRegistry::default();
+        let sand = materials.id("Sand").unwrap();
+        let water = materials.id("Water").unwrap();
+        let lava = materials.id("Lava").unwrap();
+        let stone = materials.id("Stone").unwrap();
+        let blood_stone = materials.id("BloodStone").unwrap();
+        let run = || {
+            let mut rng = fastrand::Rng::with_seed(42);
+            let mut world = CellWorld::default();
+            for y in 0..20 {
+                world.insert(0, y, Some(Cell::new(sand)));
+                world.insert(1, y + 20, Some(Cell::new(water)));
+            }
+            for x in -10..=20 {
+                world.insert(x, -1, Some(Cell::new(stone)));
+            }
+            // Emitted blood and steam boiled off by the lava are created by
+            // the simulation, shades included.
+            world.insert(5, 0, Some(materials.new_cell_with(lava, &mut rng)));
+            world.insert(6, 0, Some(Cell::new(water)));
+            world.insert(10, 30, Some(Cell::new(blood_stone)));
+            for tick in 0..60 {
+                world.advance_activity();
+                step_cells(
+                    &mut world,
+                    &materials,
+                    Gravity::default().frame(),
+                    &mut rng,
+                    (tick, 60.0),
+                );
+                step_heat(&mut world, &materials, &mut rng);
+            }
+            let mut cells: Vec<_> = world
+                .iter()
+                .map(|(pos, cell)| (pos, cell.material.0, cell.shade))
+                .collect();
+            cells.sort_unstable();
+            cells
+        };
+
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn resting_cells_are_stepped_every_tick() {
+        let materials = MaterialRegistry::default();
+        let sand = materials.id("Sand").unwrap();
+        let stone = materials.id("Stone").unwrap();
+        let mut rng = fastrand::Rng::with_seed(1);
+
+        // Whatever the tick parity, a grain that has not moved in a while
+        // falls as soon as its support is gone.
+        for resting_ticks in 1..=4 {
+            let mut world = CellWorld::default();
+            world.insert(0, 1, Some(Cell::new(sand)));
+            for x in -1..=1 {
+                world.insert(x, 0, Some(Cell::new(stone)));
+            }
+            for tick in 0..=resting_ticks {
+                if tick == resting_ticks {
+                    world.insert(0, 0, None);
+                }
+                world.advance_activity();
+                step_cells(
+                    &mut world,
+                    &materials,
+                    Gravity::default().frame(),
+                    &mut rng,
+                    (tick, 60.0),
+                );
+            }
+
+            assert!(
+                world.is_cell_empty((0, 1)),
+                "sand resting for {resting_ticks} ticks did not fall"
+            );
+        }
+    }
+
+    #[test]
+    fn emitters_keep_their_rate() {
+        let materials = MaterialRegistry::default();
+        let blood_stone = materials.id("BloodStone").unwrap();
+        let blood = materials.id("Blood").unwrap();
+        let emitter = materials.get(blood_stone).emitter.unwrap();
+        let mut rng = fastrand::Rng::with_seed(1);
+        let mut world = CellWorld::default();
+        world.insert(0, 0, Some(materials.new_cell(blood_stone)));
+
+        for tick in 0..120 {
+            world.advance_activity();
+            step_cells(
+                &mut world,
+                &materials,
+                Gravity::default().frame(),
+                &mut rng,
+                (tick, 60.0),
+            );
+        }
+
+        let emitted = world
+            .iter()
+            .filter(|(_, cell)| cell.material == blood)
+            .count();
+        assert_eq!((2.0 * emitter.rate) as usize, emitted);
+    }
+
+    #[test]
+    fn viscous_liquids_keep_flowing() {
+        let materials = MaterialRegistry::default();
+        let oil = materials.id("Oil").unwrap();
+        let stone = materials.id("Stone").unwrap();
+        let mut rng = fastrand::Rng::with_seed(1);
+        let mut world = CellWorld::default();
+        for x in -100..=100 {
+            world.insert(x, 0, Some(Cell::new(stone)));
+        }
+        world.insert(0, 1, Some(Cell::new(oil)));
+
+        // A lone drop fails its viscosity roll often, and its chunk has to
+        // stay awake through those ticks for it to flow on afterwards.
+        let mut pos = (0, 1);
+        let mut moves = 0;
+        for tick in 0..100 {
+            world.advance_activity();
+            step_cells(
+                &mut world,
+                &materials,
+                Gravity::default().frame(),
+                &mut rng,
+                (tick, 60.0),
+            );
+            let (now, _) = world.iter().find(|(_, cell)| cell.material == oil).unwrap();
+            if now != pos {
+                moves += 1;
+                pos = now;
+            }
+        }
+
+        assert!(moves > 30, "oil only moved {moves} times in 100 ticks");
     }
 }
@@ -0,0 +1,118 @@
+use crate::{
+    materials::{MaterialRegistry, Product},
+    resources::{cell_world::CellWorld, SimulationRng},
+};
+use bevy::{prelude::*, utils::HashSet};
+
+pub fn react_cells(
+    mut cell_world: ResMut<CellWorld>,
+    materials: Res<MaterialRegistry>,
+    mut rng: ResMut<SimulationRng>,
+) {
+    step_reactions(&mut cell_world, &materials, &mut rng.rng);
+}
+
+/// Applies the material file's reaction table to neighbouring pairs in the
+/// active chunks. Each cell takes part in at most one reaction per tick.
+pub fn step_reactions(
+    cell_world: &mut CellWorld,
+    materials: &MaterialRegistry,
+    rng: &mut fastrand::Rng,
+) {
+    let mut reacted = HashSet::new();
+    for chunk_pos in cell_world.active_chunks() {
+        for (x, y) in CellWorld::chunk_cell_coords(chunk_pos) {
+            let Some(cell) = cell_world.get(x, y) else {
+                continue;
+            };
+            let reactions = materials.reactions(cell.material);
+            if reactions.is_empty() || reacted.contains(&(x, y)) {
+                continue;
+            }
+
+            'neighbours: for pos in [(x, y - 1), (x - 1, y), (x + 1, y), (x, y + 1)] {
+                let Some(other) = cell_world.get(pos.0, pos.1) else {
+                    continue;
+                };
+                if reacted.contains(&pos) {
+                    continue;
+                }
+
+                for reaction in reactions {
+                    if !reaction.second.matches(other.material) {
+                        continue;
+                    }
+                    if rng.f32() >= reaction.probability {
+                        // Still able to react, so keep the chunk awake even
+                        // though nothing changed.
+                        cell_world.wake(x, y);
+                        continue;
+                    }
+
+                    apply_product(cell_world, materials, rng, (x, y), reaction.first_becomes);
+                    apply_product(cell_world, materials, rng, pos, reaction.second_becomes);
+                    reacted.insert((x, y));
+                    reacted.insert(pos);
+                    break 'neighbours;
+                }
+            }
+        }
+    }
+}
+
+fn apply_product(
+    cell_world: &mut CellWorld,
+    materials: &MaterialRegistry,
+    rng: &mut fastrand::Rng,
+    pos: (isize, isize),
+    product: Product,
+) {
+    match product {
+        Product::Keep => {}
+        Product::Remove => cell_world.insert(pos.0, pos.1, None),
+        Product::Become(material) => {
+            cell_world.insert(pos.0, pos.1, Some(materials.new_cell_with(material, rng)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::step_reactions;
+    use crate::{materials::MaterialRegistry, resources::cell_world::CellWorld};
+
+    #[test]
+    fn reaction_rows_transform_neighbours() {
+        let materials = MaterialRegistry::from_ron(
+            r#"(
+                materials: [
+                    (name: "Acid", colors: ["00ff00"], physics: Fluid),
+                    (name: "Glass", colors: ["ffffff"], physics: Static),
+                    (name: "Stone", colors: ["444444"], physics: Static),
+                ],
+                reactions: [(
+                    first: "Acid",
+                    second: AnyExcept(["Acid", "Glass"]),
+                    first_becomes: Keep,
+                    second_becomes: Remove,
+                    probability: 1.0,
+                )],
+            )"#,
+        )
+        .unwrap();
+        let acid = materials.id("Acid").unwrap();
+        let glass = materials.id("Glass").unwrap();
+        let stone = materials.id("Stone").unwrap();
+        let mut world = CellWorld::default();
+        world.insert(0, 1, Some(materials.new_cell(acid)));
+        world.insert(0, 0, Some(materials.new_cell(stone)));
+        world.insert(1, 1, Some(materials.new_cell(glass)));
+        world.advance_activity();
+
+        step_reactions(&mut world, &materials, &mut fastrand::Rng::with_seed(1));
+
+        assert!(world.is_cell_empty((0, 0)));
+        assert_eq!(Some(acid), world.get(0, 1).map(|cell| cell.material));
+        assert_eq!(Some(glass), world.get(1, 1).map(|cell| cell.material));
+    }
+}
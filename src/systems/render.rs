@@ -0,0 +1,215 @@
+use crate::{
+    enums::{BrushShape, Tool, CELL_SIZE},
+    materials::MaterialRegistry,
+    resources::{
+        cell_world::{CellRect, CellWorld, CHUNK_SIZE},
+        clipboard::Clipboard,
+        Brush, ChunkTexture, ChunkTextures, CursorPosition, EguiHoverState, ToolState,
+    },
+    utils::position_to_cell_coords,
+};
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+    sprite::Anchor,
+    utils::HashMap,
+};
+
+const EMPTY_TEXEL: [u8; 4] = [0, 0, 0, 0];
+
+/// Writes cells changed since the last frame into their chunk's texture.
+/// Textures and sprites are created when a chunk first appears and dropped
+/// together with it.
+pub fn update_chunk_textures(
+    mut commands: Commands,
+    mut cell_world: ResMut<CellWorld>,
+    mut chunk_textures: ResMut<ChunkTextures>,
+    mut images: ResMut<Assets<Image>>,
+    materials: Res<MaterialRegistry>,
+) {
+    let mut changes_by_chunk: HashMap<(isize, isize), Vec<(isize, isize)>> = HashMap::new();
+    for pos in cell_world.take_changes() {
+        let (chunk_pos, _) = CellWorld::split_coords(pos.0, pos.1);
+        changes_by_chunk.entry(chunk_pos).or_default().push(pos);
+    }
+
+    for (chunk_pos, changes) in changes_by_chunk {
+        if !cell_world.has_chunk(chunk_pos) {
+            if let Some(chunk_texture) = chunk_textures.chunks.remove(&chunk_pos) {
+                commands.entity(chunk_texture.sprite).despawn();
+                images.remove(&chunk_texture.image);
+            }
+            continue;
+        }
+
+        let chunk_texture = chunk_textures
+            .chunks
+            .entry(chunk_pos)
+            .or_insert_with(|| spawn_chunk_sprite(&mut commands, &mut images, chunk_pos));
+        let Some(image) = images.get_mut(&chunk_texture.image) else {
+            continue;
+        };
+
+        for (x, y) in changes {
+            let (_, (local_x, local_y)) = CellWorld::split_coords(x, y);
+            // Texture rows run top to bottom, world rows bottom to top.
+            let texel = (CHUNK_SIZE as usize - 1 - local_y) * CHUNK_SIZE as usize + local_x;
+            let color = cell_world.get(x, y).map_or(EMPTY_TEXEL, |cell| {
+                materials.get(cell.material).color(cell.shade)
+            });
+            image.data[texel * 4..texel * 4 + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+fn spawn_chunk_sprite(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    chunk_pos: (isize, isize),
+) -> ChunkTexture {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: CHUNK_SIZE as u32,
+            height: CHUNK_SIZE as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &EMPTY_TEXEL,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler = ImageSampler::nearest();
+    let image = images.add(image);
+
+    // Cells are centred on their grid position, so the chunk's corner sits
+    // half a cell below and left of its first cell.
+    let origin = Vec2::new(
+        (chunk_pos.0 * CHUNK_SIZE) as f32 - 0.5,
+        (chunk_pos.1 * CHUNK_SIZE) as f32 - 0.5,
+    ) * CELL_SIZE.truncate();
+    let sprite = commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(CHUNK_SIZE as f32 * CELL_SIZE.truncate()),
+                anchor: Anchor::BottomLeft,
+                ..Default::default()
+            },
+            texture: image.clone(),
+            transform: Transform::from_translation(origin.extend(0.0)),
+            ..Default::default()
+        })
+        .id();
+
+    ChunkTexture { sprite, image }
+}
+
+const BRUSH_PREVIEW_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.6);
+
+/// Outlines the brush footprint under the cursor while a tool that paints
+/// with it is selected.
+pub fn draw_brush_preview(
+    mut gizmos: Gizmos,
+    brush: Res<Brush>,
+    tools: Res<ToolState>,
+    cursor_position: Res<CursorPosition>,
+    egui_state: Res<EguiHoverState>,
+) {
+    if egui_state.is_hovered || !matches!(tools.tool, Tool::Brush | Tool::Line) {
+        return;
+    }
+
+    let (x, y) = position_to_cell_coords(cursor_position.pos);
+    let center = Vec2::new(x as f32, y as f32) * CELL_SIZE.truncate();
+    let diameter = (2 * brush.radius + 1) as f32;
+    match brush.shape {
+        BrushShape::Circle => {
+            gizmos.circle_2d(center, diameter / 2.0 * CELL_SIZE.x, BRUSH_PREVIEW_COLOR);
+        }
+        BrushShape::Square => {
+            let size = diameter * CELL_SIZE.truncate();
+            gizmos.rect_2d(center, 0.0, size, BRUSH_PREVIEW_COLOR);
+        }
+        BrushShape::Line => {
+            let size = Vec2::new(diameter, 1.0) * CELL_SIZE.truncate();
+            gizmos.rect_2d(center, 0.0, size, BRUSH_PREVIEW_COLOR);
+        }
+    }
+}
+
+const ELLIPSE_PREVIEW_SEGMENTS: usize = 64;
+
+/// Outlines the shape being dragged out by the line, rectangle, ellipse or
+/// select tool.
+pub fn draw_shape_preview(
+    mut gizmos: Gizmos,
+    tools: Res<ToolState>,
+    cursor_position: Res<CursorPosition>,
+) {
+    let Some(start) = tools.drag_start else {
+        return;
+    };
+
+    let to_world = |(x, y): (isize, isize)| Vec2::new(x as f32, y as f32) * CELL_SIZE.truncate();
+    let end = position_to_cell_coords(cursor_position.pos);
+    let center = (to_world(start) + to_world(end)) / 2.0;
+    let size = (to_world(start) - to_world(end)).abs() + CELL_SIZE.truncate();
+    match tools.tool {
+        Tool::Line => gizmos.line_2d(to_world(start), to_world(end), BRUSH_PREVIEW_COLOR),
+        Tool::Rectangle | Tool::Select => gizmos.rect_2d(center, 0.0, size, BRUSH_PREVIEW_COLOR),
+        Tool::Ellipse => gizmos.linestrip_2d(
+            (0..=ELLIPSE_PREVIEW_SEGMENTS).map(|i| {
+                let angle = i as f32 / ELLIPSE_PREVIEW_SEGMENTS as f32 * std::f32::consts::TAU;
+                center + Vec2::new(angle.cos(), angle.sin()) * size / 2.0
+            }),
+            BRUSH_PREVIEW_COLOR,
+        ),
+        Tool::Brush | Tool::Fill | Tool::Paste => {}
+    }
+}
+
+const SELECTION_COLOR: Color = Color::rgba(1.0, 0.85, 0.2, 0.8);
+/// Larger clipboards are previewed by their outline only.
+const MAX_PREVIEWED_CELLS: usize = 4096;
+
+/// Outlines the selection and, with the paste tool, the clipboard contents
+/// under the cursor in their own colours.
+pub fn draw_selection_preview(
+    mut gizmos: Gizmos,
+    tools: Res<ToolState>,
+    clipboard: Res<Clipboard>,
+    materials: Res<MaterialRegistry>,
+    cursor_position: Res<CursorPosition>,
+    egui_state: Res<EguiHoverState>,
+) {
+    let cell_rect = |rect: CellRect| {
+        let min = Vec2::new(rect.min.0 as f32, rect.min.1 as f32);
+        let max = Vec2::new(rect.max.0 as f32, rect.max.1 as f32);
+        let size = Vec2::new(rect.width() as f32, rect.height() as f32);
+        (
+            (min + max) / 2.0 * CELL_SIZE.truncate(),
+            size * CELL_SIZE.truncate(),
+        )
+    };
+
+    if let Some(selection) = tools.selection.filter(|_| tools.drag_start.is_none()) {
+        let (center, size) = cell_rect(selection);
+        gizmos.rect_2d(center, 0.0, size, SELECTION_COLOR);
+    }
+    if tools.tool != Tool::Paste || clipboard.is_empty() || egui_state.is_hovered {
+        return;
+    }
+
+    let origin = clipboard.origin(position_to_cell_coords(cursor_position.pos));
+    let (width, height) = clipboard.size();
+    let bounds = CellRect::from_corners(origin, (origin.0 + width - 1, origin.1 + height - 1));
+    let (center, size) = cell_rect(bounds);
+    gizmos.rect_2d(center, 0.0, size, SELECTION_COLOR);
+
+    for (pos, cell) in clipboard.placed(origin).take(MAX_PREVIEWED_CELLS) {
+        let [r, g, b, _] = materials.get(cell.material).color(cell.shade);
+        let (center, size) = cell_rect(CellRect::from_corners(pos, pos));
+        gizmos.rect_2d(center, 0.0, size * 0.8, Color::rgb_u8(r, g, b));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{
+    enums::Tool,
+    events::{CellEditEvents, CopySelectionEvent},
+    resources::{
+        cell_world::{CellRect, CellWorld},
+        clipboard::Clipboard,
+        edit_history::EditHistory,
+        Brush, CellTypeToSpawn, CursorPosition, EguiHoverState, ToolState,
+    },
+    shapes::flood_fill,
+    systems::input_handling::PointerButtons,
+    utils::position_to_cell_coords,
+};
+use bevy::prelude::*;
+
+/// Run condition for systems that only act while `tool` is selected.
+pub fn tool_selected(tool: Tool) -> impl Fn(Res<ToolState>) -> bool {
+    move |tools| tools.tool == tool
+}
+
+/// Starts a drag on press and, on release, paints the shape between the two
+/// cells with the left button or erases it with the right.
+pub fn use_shape_tool(
+    buttons: PointerButtons,
+    cursor_position: Res<CursorPosition>,
+    mut edits: CellEditEvents,
+    egui_state: Res<EguiHoverState>,
+    cell_type_to_spawn: Res<CellTypeToSpawn>,
+    brush: Res<Brush>,
+    mut tools: ResMut<ToolState>,
+) {
+    let cell = position_to_cell_coords(cursor_position.pos);
+    let pressed = buttons.any_just_pressed([MouseButton::Left, MouseButton::Right]);
+    if pressed && !egui_state.is_hovered && tools.drag_start.is_none() {
+        tools.drag_start = Some(cell);
+        return;
+    }
+
+    let Some(start) = tools.drag_start else {
+        return;
+    };
+    if buttons.just_released(MouseButton::Left) {
+        edits.paint(
+            tools.shape(&brush, start, cell),
+            cell_type_to_spawn.type_to_select,
+        );
+    } else if buttons.just_released(MouseButton::Right) {
+        edits.erase(tools.shape(&brush, start, cell));
+    } else {
+        return;
+    }
+    tools.drag_start = None;
+}
+
+/// Fills the empty region around the clicked cell with the left button, or
+/// erases the region of the clicked material with the right.
+pub fn use_fill_tool(
+    buttons: PointerButtons,
+    cursor_position: Res<CursorPosition>,
+    mut edits: CellEditEvents,
+    egui_state: Res<EguiHoverState>,
+    cell_type_to_spawn: Res<CellTypeToSpawn>,
+    cell_world: Res<CellWorld>,
+) {
+    let painting = buttons.just_pressed(MouseButton::Left);
+    let erasing = buttons.just_pressed(MouseButton::Right);
+    if egui_state.is_hovered || !(painting || erasing) {
+        return;
+    }
+
+    let start = position_to_cell_coords(cursor_position.pos);
+    let target = cell_world.get(start.0, start.1).map(|cell| cell.material);
+    if painting && target.is_some() || erasing && target.is_none() {
+        return;
+    }
+    let region = flood_fill(start, ToolState::MAX_FILL_CELLS, |(x, y)| {
+        cell_world.get(x, y).map(|cell| cell.material) == target
+    });
+
+    match region {
+        Some(cells) if painting => edits.paint(cells, cell_type_to_spawn.type_to_select),
+        Some(cells) => edits.erase(cells),
+        None => warn!(
+            "Region at {start:?} is larger than {} cells, not filling it",
+            ToolState::MAX_FILL_CELLS
+        ),
+    }
+}
+
+/// Drags out the selection with the left button; the right button clears it.
+pub fn use_select_tool(
+    buttons: PointerButtons,
+    cursor_position: Res<CursorPosition>,
+    egui_state: Res<EguiHoverState>,
+    mut tools: ResMut<ToolState>,
+) {
+    let cell = position_to_cell_coords(cursor_position.pos);
+    if !egui_state.is_hovered && buttons.just_pressed(MouseButton::Right) {
+        tools.selection = None;
+    }
+    if !egui_state.is_hovered && buttons.just_pressed(MouseButton::Left) {
+        tools.drag_start = Some(cell);
+    }
+    if buttons.just_released(MouseButton::Left) {
+        if let Some(start) = tools.drag_start.take() {
+            tools.selection = Some(CellRect::from_corners(start, cell));
+        }
+    }
+}
+
+/// Places the clipboard centred on the clicked cell as a single edit. Empty
+/// clipboard cells leave the world untouched.
+pub fn use_paste_tool(
+    buttons: PointerButtons,
+    cursor_position: Res<CursorPosition>,
+    egui_state: Res<EguiHoverState>,
+    clipboard: Res<Clipboard>,
+    mut history: ResMut<EditHistory>,
+    mut cell_world: ResMut<CellWorld>,
+) {
+    if egui_state.is_hovered || !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let origin = clipboard.origin(position_to_cell_coords(cursor_position.pos));
+    history.commit();
+    for (pos, cell) in clipboard.placed(origin) {
+        history.set(&mut cell_world, pos, Some(cell));
+    }
+    history.commit();
+}
+
+pub fn copy_selection(
+    mut ev_copy_selection: EventReader<CopySelectionEvent>,
+    tools: Res<ToolState>,
+    mut clipboard: ResMut<Clipboard>,
+    mut history: ResMut<EditHistory>,
+    mut cell_world: ResMut<CellWorld>,
+) {
+    for ev in ev_copy_selection.read() {
+        let Some(selection) = tools.selection else {
+            continue;
+        };
+
+        *clipboard = Clipboard::copy(&cell_world, selection);
+        if ev.cut {
+            history.commit();
+            for (pos, _) in clipboard.placed(selection.min) {
+                history.set(&mut cell_world, pos, None);
+            }
+            history.commit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tool_selected, use_paste_tool};
+    use crate::{
+        enums::{Tool, CELL_SIZE},
+        events::{RedoEvent, RemoveCellEvent, SpawnCellEvent, UndoEvent},
+        materials::MaterialRegistry,
+        resources::{
+            cell_world::{Cell, CellRect, CellWorld},
+            clipboard::Clipboard,
+            edit_history::EditHistory,
+            Brush, CellTypeToSpawn, CursorPosition, EguiHoverState, ToolState,
+        },
+        systems::{
+            cell_management::{remove_cell, spawn_cell, spawn_or_remove_cell_on_click},
+            history::{finish_edit, undo_or_redo, EditSet},
+        },
+    };
+    use bevy::prelude::*;
+
+    fn click_at(app: &mut App, cell: (isize, isize), tool: Tool) {
+        app.world.resource_mut::<ToolState>().tool = tool;
+        app.world.resource_mut::<CursorPosition>().pos =
+            Vec2::new(cell.0 as f32 + 0.5, cell.1 as f32 + 0.5) * CELL_SIZE.truncate();
+        let mut buttons = app.world.resource_mut::<Input<MouseButton>>();
+        buttons.reset_all();
+        buttons.press(MouseButton::Left);
+        app.update();
+    }
+
+    #[test]
+    fn pasting_during_a_stroke_is_its_own_step() {
+        let materials = MaterialRegistry::default();
+        let sand = materials.id("Sand").unwrap();
+        let stone = materials.id("Stone").unwrap();
+        let mut source = CellWorld::default();
+        source.insert(0, 0, Some(Cell::new(stone)));
+
+        let mut app = App::new();
+        app.insert_resource(materials)
+            .insert_resource(CellWorld::default())
+            .insert_resource(Clipboard::copy(
+                &source,
+                CellRect::from_corners((0, 0), (0, 0)),
+            ))
+            .insert_resource(CellTypeToSpawn {
+                type_to_select: sand,
+            })
+            .insert_resource(EguiHoverState::default())
+            .insert_resource(CursorPosition::default())
+            .init_resource::<EditHistory>()
+            .init_resource::<ToolState>()
+            .init_resource::<Brush>()
+            .init_resource::<Input<MouseButton>>()
+            .init_resource::<Touches>()
+            .add_event::<SpawnCellEvent>()
+            .add_event::<RemoveCellEvent>()
+            .add_event::<UndoEvent>()
+            .add_event::<RedoEvent>()
+            .configure_sets(
+                Update,
+                (EditSet::Queue, EditSet::Apply, EditSet::Direct).chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    spawn_or_remove_cell_on_click
+                        .run_if(tool_selected(Tool::Brush))
+                        .in_set(EditSet::Queue),
+                    (spawn_cell, remove_cell, finish_edit)
+                        .chain()
+                        .in_set(EditSet::Apply),
+                    (
+                        undo_or_redo,
+                        use_paste_tool.run_if(tool_selected(Tool::Paste)),
+                    )
+                        .in_set(EditSet::Direct),
+                ),
+            );
+
+        // The stroke is still held when the paste lands.
+        click_at(&mut app, (0, 0), Tool::Brush);
+        click_at(&mut app, (5, 0), Tool::Paste);
+        let material_at = |app: &App, x| {
+            app.world
+                .resource::<CellWorld>()
+                .get(x, 0)
+                .map(|cell| cell.material)
+        };
+        assert_eq!(Some(sand), material_at(&app, 0));
+        assert_eq!(Some(stone), material_at(&app, 5));
+        assert_eq!(2, app.world.resource::<EditHistory>().undo_len());
+
+        app.world.resource_mut::<Input<MouseButton>>().clear();
+        app.world.send_event(UndoEvent);
+        app.update();
+        assert_eq!(Some(sand), material_at(&app, 0));
+        assert_eq!(None, material_at(&app, 5));
+    }
+}
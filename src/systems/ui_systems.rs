@@ -1,83 +1,454 @@
-use crate::components::{Cell, MainCamera};
-use crate::enums::CellType;
-use crate::resources::cell_world::CellWorld;
-use crate::resources::{CellTypeToSpawn, CursorPosition, EguiHoverState};
+use crate::components::MainCamera;
+use crate::enums::{BrushShape, CellPhysicsType, Tool};
+use crate::events::{
+    ClearWorldEvent, CopySelectionEvent, ExportImageEvent, ImportImageEvent, LoadWorldEvent,
+    RedoEvent, SaveWorldEvent, UndoEvent,
+};
+use crate::materials::MaterialRegistry;
+use crate::resources::cell_world::{CellRect, CellWorld};
+use crate::resources::clipboard::Clipboard;
+use crate::resources::edit_history::EditHistory;
+use crate::resources::{
+    Brush, CellTypeToSpawn, CursorPosition, EguiHoverState, Gravity, SimulateWorldState,
+    SimulationRng, SimulationTick, ToolState,
+};
+use crate::scene_image::ImportPalette;
 use crate::utils::{align_to_grid, position_to_cell_coords};
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContexts};
-use strum::IntoEnumIterator;
 
 pub fn spawn_cell_type(
     mut contexts: EguiContexts,
     mut cell_type_to_spawn: ResMut<CellTypeToSpawn>,
+    materials: Res<MaterialRegistry>,
 ) {
     egui::Window::new("Cell Type").show(contexts.ctx_mut(), |ui| {
         egui::ComboBox::from_label("Select one!")
-            .selected_text(format!("{:?}", cell_type_to_spawn.type_to_select))
+            .selected_text(materials.name(cell_type_to_spawn.type_to_select))
             .show_ui(ui, |ui| {
-                for cell_type in CellType::iter() {
+                for (id, material) in materials.iter() {
                     if ui
                         .selectable_value(
                             &mut cell_type_to_spawn.type_to_select,
-                            cell_type.clone(),
-                            format!("{:?}", cell_type),
+                            id,
+                            &material.name,
                         )
                         .clicked()
                     {
-                        info!("Selected: {:?}", cell_type_to_spawn.type_to_select);
+                        info!("Selected: {}", material.name);
                     }
                 }
             });
+
+        let selected = materials.get(cell_type_to_spawn.type_to_select);
+        ui.label(format!("physics: {:?}", selected.physics));
+        ui.label(format!("density: {}", selected.density));
+        ui.label(format!("flammability: {}", selected.flammability));
+        if selected.physics == CellPhysicsType::Fluid {
+            ui.label(format!("dispersion: {}", selected.dispersion));
+            ui.label(format!("viscosity: {}", selected.viscosity));
+        }
+        if let Some(ignition_temperature) = selected.ignition_temperature {
+            ui.label(format!("ignites at: {ignition_temperature} °C"));
+            ui.label(format!("burn time: {} ticks", selected.burn_time));
+        }
+        if let Some(lifetime) = selected.lifetime {
+            ui.label(format!("lifetime: {lifetime} ticks"));
+        }
     });
 }
 
-pub fn my_cursor_system(
-    windows: Query<&Window>,
-    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+/// Toolbar for picking what the mouse does in the world, with the clipboard
+/// actions of the select and paste tools.
+pub fn tool_ui(
     mut contexts: EguiContexts,
-    mut cursor_position: ResMut<CursorPosition>,
+    mut tools: ResMut<ToolState>,
+    mut clipboard: ResMut<Clipboard>,
+    mut ev_copy_selection: EventWriter<CopySelectionEvent>,
 ) {
-    let window = windows.get_single();
-    let (camera, camera_transform) = camera_q.single();
+    egui::Window::new("Tools").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            for tool in Tool::ALL {
+                if ui
+                    .selectable_value(&mut tools.tool, tool, format!("{tool:?}"))
+                    .clicked()
+                {
+                    tools.drag_start = None;
+                }
+            }
+        });
+        ui.add_enabled(
+            matches!(tools.tool, Tool::Rectangle | Tool::Ellipse),
+            egui::Checkbox::new(&mut tools.filled, "filled"),
+        );
+
+        ui.horizontal(|ui| {
+            let selected = tools.selection.is_some();
+            if ui
+                .add_enabled(selected, egui::Button::new("Copy"))
+                .clicked()
+            {
+                ev_copy_selection.send(CopySelectionEvent { cut: false });
+            }
+            if ui.add_enabled(selected, egui::Button::new("Cut")).clicked() {
+                ev_copy_selection.send(CopySelectionEvent { cut: true });
+            }
 
-    match window {
-        Ok(window) => {
-            if let Some(world_position) = window
-                .cursor_position()
-                .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+            let copied = !clipboard.is_empty();
+            if ui.add_enabled(copied, egui::Button::new("Paste")).clicked() {
+                tools.tool = Tool::Paste;
+            }
+            if ui
+                .add_enabled(copied, egui::Button::new("Rotate 90° (R)"))
+                .clicked()
             {
-                cursor_position.pos = align_to_grid(world_position);
+                clipboard.rotate();
+            }
+            if ui
+                .add_enabled(copied, egui::Button::new("Mirror (M)"))
+                .clicked()
+            {
+                clipboard.mirror();
+            }
+        });
+    });
+}
+
+/// Undo and redo buttons, clearing the world and the history's memory cap.
+pub fn history_ui(
+    mut contexts: EguiContexts,
+    mut history: ResMut<EditHistory>,
+    mut ev_undo: EventWriter<UndoEvent>,
+    mut ev_redo: EventWriter<RedoEvent>,
+    mut ev_clear_world: EventWriter<ClearWorldEvent>,
+) {
+    egui::Window::new("Edit").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            let undo = egui::Button::new(format!("Undo ({})", history.undo_len()));
+            if ui.add_enabled(history.undo_len() > 0, undo).clicked() {
+                ev_undo.send(UndoEvent);
+            }
+            let redo = egui::Button::new(format!("Redo ({})", history.redo_len()));
+            if ui.add_enabled(history.redo_len() > 0, redo).clicked() {
+                ev_redo.send(RedoEvent);
+            }
+            if ui.button("Clear world").clicked() {
+                ev_clear_world.send(ClearWorldEvent);
+            }
+        });
+
+        let mut max_changes = history.max_changes();
+        ui.horizontal(|ui| {
+            ui.label("keep up to");
+            let cap = egui::DragValue::new(&mut max_changes)
+                .clamp_range(0..=EditHistory::DEFAULT_MAX_CHANGES * 16)
+                .speed(1000);
+            ui.add(cap);
+            ui.label("changed cells");
+        });
+        if max_changes != history.max_changes() {
+            history.set_max_changes(max_changes);
+        }
+        ui.label(format!(
+            "history: {} cells, {:.1} MB",
+            history.change_count(),
+            history.memory_bytes() as f64 / (1024.0 * 1024.0)
+        ));
+    });
+}
+
+pub fn brush_ui(mut contexts: EguiContexts, mut brush: ResMut<Brush>) {
+    egui::Window::new("Brush").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            for shape in BrushShape::ALL {
+                ui.selectable_value(&mut brush.shape, shape, format!("{shape:?}"));
+            }
+        });
+        ui.add(egui::Slider::new(&mut brush.radius, 0..=Brush::MAX_RADIUS).text("radius [ ]"));
+        ui.add(egui::Slider::new(&mut brush.density, 0.05..=1.0).text("density"));
+    });
+}
+
+pub fn simulation_controls(
+    mut contexts: EguiContexts,
+    mut state: ResMut<SimulateWorldState>,
+    tick: Res<SimulationTick>,
+    mut materials: ResMut<MaterialRegistry>,
+    mut gravity: ResMut<Gravity>,
+    mut rng: ResMut<SimulationRng>,
+    mut ticks_to_run: Local<u32>,
+) {
+    egui::Window::new("Simulation").show(contexts.ctx_mut(), |ui| {
+        ui.label(format!("tick: {}", tick.tick));
+        ui.horizontal(|ui| {
+            let label = if state.is_simulating { "Pause" } else { "Play" };
+            if ui.button(label).clicked() {
+                state.is_simulating = !state.is_simulating;
+            }
+            if ui.button("Step").clicked() {
+                state.pending_ticks += 1;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut *ticks_to_run).clamp_range(1..=10_000));
+            if ui.button("Run ticks").clicked() {
+                state.pending_ticks += (*ticks_to_run).max(1);
+            }
+        });
+        let mut speed = state.speed;
+        if ui
+            .add(
+                egui::Slider::new(
+                    &mut speed,
+                    SimulateWorldState::MIN_SPEED..=SimulateWorldState::MAX_SPEED,
+                )
+                .logarithmic(true)
+                .text("speed"),
+            )
+            .changed()
+        {
+            state.speed = speed;
+        }
+        let mut tick_rate = state.tick_rate;
+        if ui
+            .add(
+                egui::Slider::new(
+                    &mut tick_rate,
+                    SimulateWorldState::MIN_TICK_RATE..=SimulateWorldState::MAX_TICK_RATE,
+                )
+                .text("ticks/s"),
+            )
+            .changed()
+        {
+            state.tick_rate = tick_rate;
+        }
+        ui.horizontal(|ui| {
+            ui.label("seed");
+            ui.add(egui::DragValue::new(&mut rng.seed));
+            if ui.button("Reseed").clicked() {
+                let seed = rng.seed;
+                rng.reseed(seed);
+            }
+        });
+
+        gravity_controls(ui, &mut gravity);
+
+        let emitters: Vec<_> = materials
+            .iter()
+            .filter_map(|(id, material)| {
+                let emitter = material.emitter?;
+                Some((id, material.name.clone(), emitter.enabled))
+            })
+            .collect();
+        if !emitters.is_empty() {
+            ui.separator();
+            ui.label("Emitters");
+            for (id, name, mut enabled) in emitters {
+                if ui.checkbox(&mut enabled, name).changed() {
+                    materials.set_emitter_enabled(id, enabled);
+                }
             }
+        }
+    });
+}
+
+/// Arrow buttons laid out like the directions they point in, with angles in
+/// degrees. The centre button switches gravity off.
+const GRAVITY_BUTTONS: [[(&str, Option<f32>); 3]; 3] = [
+    [("↖", Some(135.0)), ("↑", Some(90.0)), ("↗", Some(45.0))],
+    [("←", Some(180.0)), ("0", None), ("→", Some(0.0))],
+    [("↙", Some(-135.0)), ("↓", Some(-90.0)), ("↘", Some(-45.0))],
+];
+
+/// Only writes to `gravity` when the user changes something, since every
+/// change wakes the whole world.
+fn gravity_controls(ui: &mut egui::Ui, gravity: &mut ResMut<Gravity>) {
+    ui.separator();
+    ui.label("Gravity");
+    egui::Grid::new("gravity_directions").show(ui, |ui| {
+        for row in GRAVITY_BUTTONS {
+            for (label, angle) in row {
+                if ui.button(label).clicked() {
+                    match angle {
+                        Some(angle) => {
+                            gravity.set_angle(angle);
+                            if gravity.magnitude <= 0.0 {
+                                gravity.magnitude = Gravity::DEFAULT_MAGNITUDE;
+                            }
+                        }
+                        None => gravity.magnitude = 0.0,
+                    }
+                }
+            }
+            ui.end_row();
+        }
+    });
+
+    let mut angle = gravity.angle();
+    if ui
+        .add(egui::Slider::new(&mut angle, -180.0..=180.0).text("angle"))
+        .changed()
+    {
+        gravity.set_angle(angle);
+    }
+    let mut magnitude = gravity.magnitude;
+    if ui
+        .add(egui::Slider::new(&mut magnitude, 0.0..=Gravity::MAX_MAGNITUDE).text("strength"))
+        .changed()
+    {
+        gravity.magnitude = magnitude;
+    }
+}
 
-            egui::Window::new("Cursor Position aligned to grid").show(contexts.ctx_mut(), |ui| {
-                ui.label(cursor_position.pos.to_string());
+pub struct WorldFilePanel {
+    path: String,
+    image_path: String,
+    export_scale: u32,
+    export_region: bool,
+    region_corner_a: (isize, isize),
+    region_corner_b: (isize, isize),
+}
+
+impl Default for WorldFilePanel {
+    fn default() -> Self {
+        Self {
+            path: "world.sand".to_string(),
+            image_path: "scene.png".to_string(),
+            export_scale: 1,
+            export_region: false,
+            region_corner_a: (0, 0),
+            region_corner_b: (63, 63),
+        }
+    }
+}
+
+pub fn world_file_ui(
+    mut contexts: EguiContexts,
+    mut panel: Local<WorldFilePanel>,
+    mut palette: ResMut<ImportPalette>,
+    mut ev_save_world: EventWriter<SaveWorldEvent>,
+    mut ev_load_world: EventWriter<LoadWorldEvent>,
+    mut ev_import_image: EventWriter<ImportImageEvent>,
+    mut ev_export_image: EventWriter<ExportImageEvent>,
+) {
+    let panel = &mut *panel;
+
+    egui::Window::new("World").show(contexts.ctx_mut(), |ui| {
+        ui.text_edit_singleline(&mut panel.path);
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                ev_save_world.send(SaveWorldEvent {
+                    path: panel.path.clone(),
+                });
+            }
+            if ui.button("Load").clicked() {
+                ev_load_world.send(LoadWorldEvent {
+                    path: panel.path.clone(),
+                });
+            }
+        });
+
+        ui.separator();
+        ui.text_edit_singleline(&mut panel.image_path);
+        if ui.button("Import PNG").clicked() {
+            ev_import_image.send(ImportImageEvent {
+                path: panel.image_path.clone(),
+            });
+        }
+        ui.checkbox(&mut palette.ignore_transparent, "Ignore transparent pixels");
+        ui.collapsing("Palette", |ui| {
+            for entry in palette.entries.iter_mut() {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut entry.enabled, &entry.name);
+                    ui.color_edit_button_srgb(&mut entry.color);
+                });
+            }
+        });
+
+        ui.separator();
+        if ui.button("Export PNG").clicked() {
+            ev_export_image.send(ExportImageEvent {
+                path: panel.image_path.clone(),
+                region: panel
+                    .export_region
+                    .then(|| CellRect::from_corners(panel.region_corner_a, panel.region_corner_b)),
+                scale: panel.export_scale.max(1),
             });
         }
-        Err(_) => return,
+        ui.add(egui::Slider::new(&mut panel.export_scale, 1..=16).text("pixels per cell"));
+        ui.checkbox(&mut panel.export_region, "Only export region");
+        if panel.export_region {
+            ui.horizontal(|ui| {
+                ui.label("from");
+                ui.add(egui::DragValue::new(&mut panel.region_corner_a.0));
+                ui.add(egui::DragValue::new(&mut panel.region_corner_a.1));
+            });
+            ui.horizontal(|ui| {
+                ui.label("to");
+                ui.add(egui::DragValue::new(&mut panel.region_corner_b.0));
+                ui.add(egui::DragValue::new(&mut panel.region_corner_b.1));
+            });
+        }
+    });
+}
+
+/// Tracks the mouse cursor, or the first finger on a touchscreen, including
+/// the frame it is lifted.
+pub fn my_cursor_system(
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    touches: Res<Touches>,
+    mut contexts: EguiContexts,
+    mut cursor_position: ResMut<CursorPosition>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let (camera, camera_transform) = camera_q.single();
+    let touch_position = touches.first_pressed_position().or_else(|| {
+        touches
+            .iter_just_released()
+            .next()
+            .map(|touch| touch.position())
+    });
+
+    if let Some(world_position) = touch_position
+        .or_else(|| window.cursor_position())
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    {
+        cursor_position.pos = align_to_grid(world_position);
     }
+
+    egui::Window::new("Cursor Position aligned to grid").show(contexts.ctx_mut(), |ui| {
+        ui.label(cursor_position.pos.to_string());
+    });
 }
 
 pub fn show_cell_count(mut contexts: EguiContexts, cell_count: ResMut<CellWorld>) {
     egui::Window::new("Cell count").show(contexts.ctx_mut(), |ui| {
-        ui.label(cell_count.cell_count.to_string())
+        ui.label(cell_count.cell_count.to_string());
+        ui.label(format!(
+            "chunks: {} ({} active)",
+            cell_count.chunk_count(),
+            cell_count.active_chunk_count()
+        ))
     });
 }
 
 pub fn check_is_empty_on_mouse_pos(
     cursor_positions: Res<CursorPosition>,
     world: Res<CellWorld>,
+    materials: Res<MaterialRegistry>,
     mut contexts: EguiContexts,
-    cells_query: Query<&Cell>,
 ) {
     let grid_pos = position_to_cell_coords(cursor_positions.pos);
     let value = world.get(grid_pos.0, grid_pos.1);
     match value {
-        Some(e) => {
-            if let Ok(cell) = cells_query.get(e) {
-                egui::Window::new("Is empty on mouse position:").show(contexts.ctx_mut(), |ui| {
-                    ui.label(format!("{:?}", cell.cell_type))
-                });
-            }
+        Some(cell) => {
+            egui::Window::new("Is empty on mouse position:").show(contexts.ctx_mut(), |ui| {
+                ui.label(materials.name(cell.material));
+                ui.label(format!("{:.1} °C", cell.temperature))
+            });
         }
         None => {
             egui::Window::new("Is empty on mouse position:")
@@ -86,11 +457,13 @@ pub fn check_is_empty_on_mouse_pos(
     }
 }
 
-pub fn cell_list_ui(query: Query<(&Cell, &Transform)>, mut contexts: EguiContexts) {
+/// Debug window listing every cell; not added to the app by default.
+#[allow(dead_code)]
+pub fn cell_list_ui(world: Res<CellWorld>, mut contexts: EguiContexts) {
     egui::Window::new("Cells list:").show(contexts.ctx_mut(), |ui| {
         egui::ScrollArea::vertical().show(ui, |ui| {
-            for (cell, transform) in query.iter() {
-                ui.label(format!("{:?}, {:?}", cell, transform.translation));
+            for (pos, cell) in world.iter() {
+                ui.label(format!("{:?}, {:?}", cell, pos));
             }
         });
     });
@@ -98,4 +471,5 @@ pub fn cell_list_ui(query: Query<(&Cell, &Transform)>, mut contexts: EguiContext
 
 pub fn check_egui_hover(mut contexts: EguiContexts, mut state: ResMut<EguiHoverState>) {
     state.is_hovered = contexts.ctx_mut().is_pointer_over_area();
+    state.is_typing = contexts.ctx_mut().wants_keyboard_input();
 }
@@ -0,0 +1,111 @@
+use crate::{
+    components::MainCamera,
+    events::{ExportImageEvent, ImportImageEvent, LoadWorldEvent, SaveWorldEvent},
+    materials::MaterialRegistry,
+    resources::{cell_world::CellWorld, edit_history::EditHistory},
+    sand_format::{read_world, write_world, SandFormatError},
+    scene_image::{export_png, import_png, ImportPalette},
+    utils::{get_screen_center, position_to_cell_coords},
+};
+use bevy::prelude::*;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+};
+
+pub fn save_world(
+    mut ev_save_world: EventReader<SaveWorldEvent>,
+    cell_world: Res<CellWorld>,
+    materials: Res<MaterialRegistry>,
+) {
+    for ev in ev_save_world.read() {
+        let result = File::create(&ev.path)
+            .map_err(SandFormatError::from)
+            .and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                write_world(&cell_world, &materials, &mut writer)?;
+                writer.flush()?;
+                Ok(())
+            });
+
+        match result {
+            Ok(()) => info!("Saved {} cells to {}", cell_world.cell_count, ev.path),
+            Err(err) => error!("Failed to save world to {}: {}", ev.path, err),
+        }
+    }
+}
+
+pub fn load_world(
+    mut ev_load_world: EventReader<LoadWorldEvent>,
+    mut cell_world: ResMut<CellWorld>,
+    materials: Res<MaterialRegistry>,
+    mut history: ResMut<EditHistory>,
+) {
+    for ev in ev_load_world.read() {
+        let result = File::open(&ev.path)
+            .map_err(SandFormatError::from)
+            .and_then(|file| read_world(&mut BufReader::new(file), &materials));
+
+        match result {
+            Ok(cells) => {
+                cell_world.clear();
+                history.clear();
+                for ((x, y), cell) in cells {
+                    cell_world.insert(x, y, Some(cell));
+                }
+                info!("Loaded {} cells from {}", cell_world.cell_count, ev.path);
+            }
+            Err(err) => error!("Failed to load world from {}: {}", ev.path, err),
+        }
+    }
+}
+
+/// Places imported images centred on the screen, replacing whatever cells
+/// they cover, as a single edit that can be undone.
+pub fn import_scene_image(
+    mut ev_import_image: EventReader<ImportImageEvent>,
+    mut cell_world: ResMut<CellWorld>,
+    mut history: ResMut<EditHistory>,
+    palette: Res<ImportPalette>,
+    materials: Res<MaterialRegistry>,
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) {
+    let Some(ev) = ev_import_image.read().last() else {
+        return;
+    };
+
+    let cells = match import_png(&ev.path, &palette) {
+        Ok(cells) => cells,
+        Err(err) => {
+            error!("Failed to import {}: {}", ev.path, err);
+            return;
+        }
+    };
+
+    let center = position_to_cell_coords(get_screen_center(windows, camera_q));
+    let width = cells.iter().map(|&((x, _), _)| x + 1).max().unwrap_or(0);
+    let height = cells.iter().map(|&((_, y), _)| y + 1).max().unwrap_or(0);
+    let origin = (center.0 - width / 2, center.1 - height / 2);
+
+    history.commit();
+    for &((x, y), material) in &cells {
+        let cell = materials.new_cell(material);
+        history.set(&mut cell_world, (origin.0 + x, origin.1 + y), Some(cell));
+    }
+    history.commit();
+    info!("Imported {} cells from {}", cells.len(), ev.path);
+}
+
+pub fn export_scene_image(
+    mut ev_export_image: EventReader<ExportImageEvent>,
+    cell_world: Res<CellWorld>,
+    materials: Res<MaterialRegistry>,
+) {
+    for ev in ev_export_image.read() {
+        match export_png(&cell_world, &materials, ev.region, ev.scale, &ev.path) {
+            Ok(()) => info!("Exported world to {}", ev.path),
+            Err(err) => error!("Failed to export {}: {}", ev.path, err),
+        }
+    }
+}